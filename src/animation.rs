@@ -0,0 +1,253 @@
+use crate::{
+    math::{Aabb, Vec3},
+    Camera, HitRecord, Hittable, Projection, RTError, Ray, StableHasher,
+};
+use std::ops::{Add, Mul};
+
+// How a track goes from one key to the next: in a straight line, or along a Catmull-Rom spline
+// going through all the keys, which is smooth at the keys too
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+}
+
+// What can be animated: a weighted sum of values is again a value
+pub trait Animatable: Copy + Add<Output = Self> + Mul<f64, Output = Self> {}
+
+impl<T: Copy + Add<Output = T> + Mul<f64, Output = T>> Animatable for T {}
+
+// Values of something at given times, in seconds. Before the first key and after the last one the
+// value stays the one of the key
+#[derive(Debug, PartialEq, Clone)]
+pub struct Track<T: Animatable> {
+    pub interpolation: Interpolation,
+    keys: Vec<(f64, T)>,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Track {
+            interpolation,
+            keys: vec![],
+        }
+    }
+
+    // Keys can be added in any order, a key at the time of another one replaces it. Keys at a time
+    // that is not finite are left out, they would have no place among the others
+    pub fn add_key(&mut self, time: f64, value: T) {
+        if !time.is_finite() {
+            return;
+        }
+        let i = self.keys.partition_point(|(t, _)| *t < time);
+        if self.keys.get(i).is_some_and(|(t, _)| *t == time) {
+            self.keys[i].1 = value;
+        } else {
+            self.keys.insert(i, (time, value));
+        }
+    }
+
+    pub fn keys(&self) -> &[(f64, T)] {
+        &self.keys
+    }
+
+    // None when the track has no key or the time is not a number
+    pub fn value_at(&self, time: f64) -> Option<T> {
+        if time.is_nan() {
+            return None;
+        }
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+
+        // Between the keys i and i + 1
+        let i = self.keys.partition_point(|(t, _)| *t <= time) - 1;
+        let (t1, p1) = self.keys[i];
+        let (t2, p2) = self.keys[i + 1];
+        let u = (time - t1) / (t2 - t1);
+
+        Some(match self.interpolation {
+            Interpolation::Linear => p1 * (1.0 - u) + p2 * u,
+            Interpolation::CatmullRom => {
+                // The first and last keys are repeated to have neighbours on both sides
+                let p0 = self.keys[i.saturating_sub(1)].1;
+                let p3 = self.keys[(i + 2).min(self.keys.len() - 1)].1;
+                let (u2, u3) = (u * u, u * u * u);
+                p0 * (0.5 * (-u + 2.0 * u2 - u3))
+                    + p1 * (0.5 * (2.0 - 5.0 * u2 + 3.0 * u3))
+                    + p2 * (0.5 * (u + 4.0 * u2 - 3.0 * u3))
+                    + p3 * (0.5 * (u3 - u2))
+            }
+        })
+    }
+}
+
+// Moves a perspective camera over time. Tracks without keys keep what the camera had
+#[derive(Debug, PartialEq, Clone)]
+pub struct CameraAnimation {
+    pub position: Track<Vec3>,
+    pub lookat: Track<Vec3>,
+    pub vfov: Track<f64>,
+    pub focus_dist: Track<f64>,
+    pub vup: Vec3,
+}
+
+impl CameraAnimation {
+    pub fn new(interpolation: Interpolation, vup: Vec3) -> Self {
+        CameraAnimation {
+            position: Track::new(interpolation),
+            lookat: Track::new(interpolation),
+            vfov: Track::new(interpolation),
+            focus_dist: Track::new(interpolation),
+            vup,
+        }
+    }
+
+    // `camera` at `time`, with the same aspect ratio, aperture, lens and exposure. The other
+    // projections are made differently, so only perspective cameras can be animated
+    pub fn camera_at(&self, camera: &Camera, time: f64) -> Result<Camera, RTError> {
+        if camera.projection != Projection::Perspective {
+            return Err(RTError::InvalidArgument(format!(
+                "only perspective cameras can be animated, not {:?} ones",
+                camera.projection
+            )));
+        }
+        let position = self.position.value_at(time).unwrap_or(camera.origin);
+        let lookat = self
+            .lookat
+            .value_at(time)
+            .unwrap_or(camera.origin - camera.focus_dist * camera.w);
+
+        let mut animated = Camera::new(
+            position,
+            lookat,
+            self.vup,
+            self.vfov.value_at(time).unwrap_or(camera.vfov),
+            camera.horizontal.length() / camera.vertical.length(),
+            camera.aperture,
+            self.focus_dist.value_at(time).unwrap_or(camera.focus_dist),
+        );
+        animated.lens = camera.lens.clone();
+        animated.exposure = camera.exposure;
+        Ok(animated)
+    }
+}
+
+// An object moved by `offset`, for example the value of a `Track<Vec3>` at the time of a frame
+pub struct Translated {
+    pub object: Box<dyn Hittable>,
+    pub offset: Vec3,
+}
+
+impl Translated {
+    pub fn new(object: Box<dyn Hittable>, offset: Vec3) -> Self {
+        Translated { object, offset }
+    }
+
+    pub fn new_boxed(object: Box<dyn Hittable>, offset: Vec3) -> Box<Self> {
+        Box::new(Self::new(object, offset))
+    }
+}
+
+impl Hittable for Translated {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let moved = Ray::new(r.origin - self.offset, r.direction);
+        let mut hit = self.object.hit(&moved, t_min, t_max)?;
+        hit.point += self.offset;
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.object.bounding_box()?;
+        Some(Aabb::new(bbox.min + self.offset, bbox.max + self.offset))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Translated", self.offset));
+        self.object.fingerprint(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_go_through_their_keys() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom].iter() {
+            let mut track = Track::new(*interpolation);
+            assert_eq!(track.value_at(1.0), None);
+            track.add_key(2.0, 4.0);
+            track.add_key(0.0, 0.0);
+            track.add_key(1.0, 1.0);
+            track.add_key(3.0, 9.0);
+
+            assert_eq!(track.keys().len(), 4);
+            for (time, value) in
+                [(-1.0, 0.0), (0.0, 0.0), (1.0, 1.0), (2.0, 4.0), (5.0, 9.0)].iter()
+            {
+                assert!((track.value_at(*time).unwrap() - value).abs() < 1e-12);
+            }
+            assert_eq!(track.value_at(f64::NAN), None);
+            assert_eq!(track.value_at(f64::INFINITY), Some(9.0));
+        }
+
+        // Points on a line stay on it, evenly spaced ones are followed at the same speed
+        let mut track = Track::new(Interpolation::CatmullRom);
+        for i in 0..4 {
+            track.add_key(i as f64, Vec3::new(i as f64, 2.0 * i as f64, 0.0));
+        }
+        let middle = track.value_at(1.5).unwrap();
+        assert!((middle - Vec3::new(1.5, 3.0, 0.0)).length() < 1e-12);
+
+        let mut track = Track::new(Interpolation::Linear);
+        track.add_key(0.0, 1.0);
+        track.add_key(2.0, 3.0);
+        track.add_key(2.0, 5.0);
+        assert!((track.value_at(0.5).unwrap() - 2.0).abs() < 1e-12);
+
+        // Keys out of time do not break the order of the others
+        track.add_key(f64::NAN, 7.0);
+        track.add_key(f64::INFINITY, 7.0);
+        track.add_key(1.0, 4.0);
+        assert_eq!(track.keys(), &[(0.0, 1.0), (1.0, 4.0), (2.0, 5.0)]);
+    }
+
+    #[test]
+    fn animated_cameras_keep_their_settings() {
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        let mut animation = CameraAnimation::new(Interpolation::Linear, vup);
+        animation.position.add_key(0.0, Vec3::new(0.0, 0.0, 0.0));
+        animation.position.add_key(1.0, Vec3::new(2.0, 0.0, 0.0));
+
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            vup,
+            1.0,
+            2.0,
+            0.1,
+            3.0,
+        );
+        camera.lens.tilt = (0.2, 0.0);
+        camera.lens.cat_eye = 0.5;
+        let animated = animation.camera_at(&camera, 0.5).unwrap();
+        assert!((animated.origin - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert_eq!(animated.lens, camera.lens);
+        assert_eq!(animated.projection, Projection::Perspective);
+        assert!((animated.vfov - camera.vfov).abs() < 1e-12);
+
+        let orthographic = Camera::new_orthographic(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            vup,
+            2.0,
+            1.0,
+        );
+        assert!(animation.camera_at(&orthographic, 0.5).is_err());
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{clamp, math::Vec3, variance_buffer, Camera, Color, Image, RTError, Ray, World};
+use exr::prelude::FlatSamples;
+use image::ImageBuffer;
+use std::collections::HashMap;
+
+// Auxiliary buffers describing what is first hit by the samples of each pixel, stored row after
+// row starting from the bottom one like `Image`, see `render_with_aovs`. Pixels seeing the
+// background have a black albedo, a null normal and position, an infinite depth and the IDs 0. IDs
+// of the objects follow `World::hit_object` from 1, the ones of the materials are `Material::id`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Aovs {
+    pub width: u32,
+    pub height: u32,
+    pub albedo: Vec<Color>,
+    pub normal: Vec<Vec3>,
+    pub depth: Vec<f64>,
+    pub position: Vec<Vec3>,
+    pub material_id: Vec<u32>,
+    pub object_id: Vec<u32>,
+}
+
+impl Aovs {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Aovs {
+            width,
+            height,
+            albedo: vec![Color::new(0.0, 0.0, 0.0); size],
+            normal: vec![Vec3::new(0.0, 0.0, 0.0); size],
+            depth: vec![f64::INFINITY; size],
+            position: vec![Vec3::new(0.0, 0.0, 0.0); size],
+            material_id: vec![0; size],
+            object_id: vec![0; size],
+        }
+    }
+
+    pub fn index(&self, width: u32, height: u32) -> usize {
+        (height * self.width + width) as usize
+    }
+}
+
+// What the ray of a sample hits first, see `AovSamples`
+pub(crate) struct FirstHit {
+    albedo: Color,
+    normal: Vec3,
+    depth: f64,
+    position: Vec3,
+    material_id: u32,
+    object_id: u32,
+}
+
+// Sums of the first hits of the samples of a render, each spread over the pixels with the weights of
+// the reconstruction filter like its color. The negative lobes of some filters are left out, they
+// would take albedos and normals out of their range
+pub(crate) struct AovSamples {
+    sums: Aovs,
+    weights: Vec<f64>,
+    // Of the samples that hit something, and of the one that gave its ID to the pixel
+    hit_weights: Vec<f64>,
+    id_weights: Vec<f64>,
+    // IDs of the materials met so far, by address, they take a while to compute
+    material_ids: HashMap<*const (), u32>,
+}
+
+impl AovSamples {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        let mut sums = Aovs::new(width, height);
+        sums.depth = vec![0.0; size];
+        AovSamples {
+            sums,
+            weights: vec![0.0; size],
+            hit_weights: vec![0.0; size],
+            id_weights: vec![0.0; size],
+            material_ids: HashMap::new(),
+        }
+    }
+
+    // The depth is the distance along the axis of the camera, not along the ray
+    pub(crate) fn first_hit<F>(
+        &mut self,
+        world: &World<F>,
+        camera: &Camera,
+        ray: &Ray,
+    ) -> Option<FirstHit>
+    where
+        F: Fn(&Ray) -> Color,
+    {
+        let (hit_record, object) = world.hit_object(ray, 0.001, f64::INFINITY)?;
+        let material = hit_record.material;
+        let material_id = *self
+            .material_ids
+            .entry(material as *const _ as *const ())
+            .or_insert_with(|| material.id());
+        Some(FirstHit {
+            albedo: hit_record.material.albedo(ray, &hit_record),
+            normal: hit_record.normal,
+            depth: Vec3::dot(&(hit_record.point - ray.origin), &-camera.w),
+            position: hit_record.point,
+            material_id,
+            object_id: object as u32 + 1,
+        })
+    }
+
+    pub(crate) fn add(&mut self, width: u32, height: u32, weight: f64, hit: &Option<FirstHit>) {
+        if weight <= 0.0 {
+            return;
+        }
+        let i = self.sums.index(width, height);
+        self.weights[i] += weight;
+
+        if let Some(hit) = hit {
+            self.hit_weights[i] += weight;
+            self.sums.albedo[i].vec += weight * hit.albedo.vec;
+            self.sums.normal[i] += weight * hit.normal;
+            self.sums.depth[i] += weight * hit.depth;
+            self.sums.position[i] += weight * hit.position;
+            // The IDs can not be averaged, they are the ones of the sample closest to the center
+            if weight > self.id_weights[i] {
+                self.id_weights[i] = weight;
+                self.sums.material_id[i] = hit.material_id;
+                self.sums.object_id[i] = hit.object_id;
+            }
+        }
+    }
+
+    // The albedo is averaged over all the samples, the background counting as black. The other
+    // buffers are averaged over the samples that hit something, unless most of them did not and
+    // the pixel is taken as background
+    pub(crate) fn finish(self) -> Aovs {
+        let mut aovs = self.sums;
+        for i in 0..self.weights.len() {
+            let (weight, hit_weight) = (self.weights[i], self.hit_weights[i]);
+            if weight > 0.0 {
+                aovs.albedo[i].vec = aovs.albedo[i].vec / weight;
+            }
+            if hit_weight > 0.0 && 2.0 * hit_weight >= weight {
+                let normal = aovs.normal[i];
+                aovs.normal[i] = if normal.length_squared() > 0.0 {
+                    Vec3::unit(normal)
+                } else {
+                    normal
+                };
+                aovs.depth[i] /= hit_weight;
+                aovs.position[i] = aovs.position[i] / hit_weight;
+            } else {
+                aovs.normal[i] = Vec3::new(0.0, 0.0, 0.0);
+                aovs.depth[i] = f64::INFINITY;
+                aovs.position[i] = Vec3::new(0.0, 0.0, 0.0);
+                aovs.material_id[i] = 0;
+                aovs.object_id[i] = 0;
+            }
+        }
+        aovs
+    }
+}
+
+// Write each buffer as a PNG image named after `prefix`, for a look at them: normals are mapped
+// from [-1, 1] to [0, 1], depths and positions are scaled to the range they cover and every ID
+// gets its own color
+pub fn write_aovs_to_files(prefix: &str, aovs: &Aovs) -> Result<(), RTError> {
+    let max_depth = aovs
+        .depth
+        .iter()
+        .cloned()
+        .filter(|d| d.is_finite())
+        .fold(0.0, f64::max);
+    let (min_position, max_position) = aovs
+        .position
+        .iter()
+        .zip(aovs.depth.iter())
+        .filter(|(_, d)| d.is_finite())
+        .fold(
+            (
+                Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(min, max), (p, _)| (Vec3::min(&min, p), Vec3::max(&max, p)),
+        );
+    let extent = max_position - min_position;
+
+    write_buffer(&format!("{}-albedo.png", prefix), aovs, |i| {
+        let albedo = aovs.albedo[i];
+        [albedo.r().sqrt(), albedo.g().sqrt(), albedo.b().sqrt()]
+    })?;
+    write_buffer(&format!("{}-normal.png", prefix), aovs, |i| {
+        let n = 0.5 * aovs.normal[i] + Vec3::new(0.5, 0.5, 0.5);
+        [n.x, n.y, n.z]
+    })?;
+    write_buffer(&format!("{}-depth.png", prefix), aovs, |i| {
+        let depth = if aovs.depth[i].is_finite() && max_depth > 0.0 {
+            1.0 - aovs.depth[i] / max_depth
+        } else {
+            0.0
+        };
+        [depth, depth, depth]
+    })?;
+    write_buffer(&format!("{}-position.png", prefix), aovs, |i| {
+        if !aovs.depth[i].is_finite() {
+            return [0.0, 0.0, 0.0];
+        }
+        let p = aovs.position[i] - min_position;
+        let scale = |value: f64, extent: f64| if extent > 0.0 { value / extent } else { 0.5 };
+        [
+            scale(p.x, extent.x),
+            scale(p.y, extent.y),
+            scale(p.z, extent.z),
+        ]
+    })?;
+    write_buffer(&format!("{}-material-id.png", prefix), aovs, |i| {
+        id_color(aovs.material_id[i])
+    })?;
+    write_buffer(&format!("{}-object-id.png", prefix), aovs, |i| {
+        id_color(aovs.object_id[i])
+    })
+}
+
+// Write the image and the buffers as the layers of an OpenEXR file, in full precision: the image in
+// the "beauty" layer then the "albedo", "normal", "depth", "position", "material_id" and "object_id"
+// layers, the IDs as integers, plus the "variance" of the pixels of the image for the denoiser
+pub fn write_layers_to_exr(path: &str, img: &Image, aovs: &Aovs) -> Result<(), RTError> {
+    use exr::prelude::{
+        AnyChannel, AnyChannels, Encoding, Image as ExrImage, ImageAttributes, IntegerBounds,
+        Layer, LayerAttributes, SmallVec, WritableImage,
+    };
+
+    // EXR images are stored from the top row down
+    let rows = |h: u32| img.height - 1 - h;
+    let size = (img.width as usize, img.height as usize);
+    let pixels = (0..img.height).flat_map(|h| (0..img.width).map(move |w| (w, rows(h))));
+
+    let floats =
+        |values: Vec<f64>| FlatSamples::F32(values.into_iter().map(|v| v as f32).collect());
+    let color_channels = |colors: Vec<Vec3>, names: [&str; 3]| {
+        let channels = names
+            .iter()
+            .enumerate()
+            .map(|(c, name)| {
+                let values = colors.iter().map(|v| [v.x, v.y, v.z][c]).collect();
+                AnyChannel::new(*name, floats(values))
+            })
+            .collect();
+        AnyChannels::sort(SmallVec::from_vec(channels))
+    };
+    let layer = |name: &str, channels| {
+        Layer::new(
+            size,
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            channels,
+        )
+    };
+    let buffer = |values: &dyn Fn(usize) -> Vec3| {
+        pixels
+            .clone()
+            .map(|(w, h)| values(aovs.index(w, h)))
+            .collect::<Vec<_>>()
+    };
+    let ids = |ids: &[u32]| {
+        let values = pixels.clone().map(|(w, h)| ids[aovs.index(w, h)]).collect();
+        AnyChannels::sort(SmallVec::from_vec(vec![AnyChannel::new(
+            "id",
+            FlatSamples::U32(values),
+        )]))
+    };
+
+    let beauty = pixels
+        .clone()
+        .map(|(w, h)| img.get_color_pixel(w, h).vec)
+        .collect();
+    let depth = pixels
+        .clone()
+        .map(|(w, h)| aovs.depth[aovs.index(w, h)])
+        .collect();
+    let variance = variance_buffer(img);
+    let variance = pixels
+        .clone()
+        .map(|(w, h)| variance[aovs.index(w, h)])
+        .collect();
+    let single = |name: &str, values| {
+        AnyChannels::sort(SmallVec::from_vec(vec![AnyChannel::new(
+            name,
+            floats(values),
+        )]))
+    };
+
+    let layers = vec![
+        layer("beauty", color_channels(beauty, ["R", "G", "B"])),
+        layer(
+            "albedo",
+            color_channels(buffer(&|i| aovs.albedo[i].vec), ["R", "G", "B"]),
+        ),
+        layer(
+            "normal",
+            color_channels(buffer(&|i| aovs.normal[i]), ["X", "Y", "Z"]),
+        ),
+        layer("depth", single("Z", depth)),
+        layer(
+            "position",
+            color_channels(buffer(&|i| aovs.position[i]), ["X", "Y", "Z"]),
+        ),
+        layer("material_id", ids(&aovs.material_id)),
+        layer("object_id", ids(&aovs.object_id)),
+        layer("variance", single("Y", variance)),
+    ];
+
+    let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(size));
+    ExrImage::from_layers(attributes, layers)
+        .write()
+        .to_file(path)
+        .map_err(RTError::Exr)
+}
+
+// Read back a file written by `write_layers_to_exr`: the image, with one sample per pixel, the
+// buffers and the variance of the pixels
+pub fn read_layers_from_exr(path: &str) -> Result<(Image, Aovs, Vec<f64>), RTError> {
+    use exr::prelude::read_all_flat_layers_from_file;
+
+    let exr = read_all_flat_layers_from_file(path).map_err(RTError::Exr)?;
+    let size = exr.attributes.display_window.size;
+    let (width, height) = (size.width() as u32, size.height() as u32);
+
+    // Samples of a channel, with the index of each pixel of our buffers, from the bottom row up
+    let samples = |layer: &str, channel: &str| -> Result<(&FlatSamples, Vec<usize>), RTError> {
+        let samples = &exr
+            .layer_data
+            .iter()
+            .filter(|l| {
+                l.attributes.layer_name.as_ref().map(|n| n.to_string()) == Some(layer.to_string())
+            })
+            .flat_map(|l| l.channel_data.list.iter())
+            .find(|c| c.name.to_string() == channel)
+            .ok_or_else(|| {
+                RTError::InvalidArgument(format!("{} has no channel {}.{}", path, layer, channel))
+            })?
+            .sample_data;
+        if samples.len() != (width * height) as usize {
+            return Err(RTError::InvalidArgument(format!(
+                "{} has a channel {}.{} of the wrong size",
+                path, layer, channel
+            )));
+        }
+        let indices = (0..height)
+            .flat_map(|h| (0..width).map(move |w| ((height - 1 - h) * width + w) as usize))
+            .collect();
+        Ok((samples, indices))
+    };
+    let channel = |layer: &str, channel: &str| -> Result<Vec<f64>, RTError> {
+        let (samples, indices) = samples(layer, channel)?;
+        Ok(indices
+            .into_iter()
+            .map(|i| samples.value_by_flat_index(i).to_f32() as f64)
+            .collect())
+    };
+    let vectors = |layer: &str, names: [&str; 3]| -> Result<Vec<Vec3>, RTError> {
+        let (x, y, z) = (
+            channel(layer, names[0])?,
+            channel(layer, names[1])?,
+            channel(layer, names[2])?,
+        );
+        Ok((0..x.len()).map(|i| Vec3::new(x[i], y[i], z[i])).collect())
+    };
+    // IDs are integers, going through floats would lose the ones above 2^24
+    let ids = |layer: &str| -> Result<Vec<u32>, RTError> {
+        match samples(layer, "id")? {
+            (FlatSamples::U32(ids), indices) => Ok(indices.into_iter().map(|i| ids[i]).collect()),
+            _ => Err(RTError::InvalidArgument(format!(
+                "{} has a channel {}.id which is not made of integers",
+                path, layer
+            ))),
+        }
+    };
+
+    let beauty = vectors("beauty", ["R", "G", "B"])?;
+    let mut img = Image::new(width, height);
+    for h in 0..height {
+        for w in 0..width {
+            img.add_pixel(w, h, Color::new_with_vec(beauty[(h * width + w) as usize]));
+        }
+    }
+
+    let aovs = Aovs {
+        width,
+        height,
+        albedo: vectors("albedo", ["R", "G", "B"])?
+            .into_iter()
+            .map(Color::new_with_vec)
+            .collect(),
+        normal: vectors("normal", ["X", "Y", "Z"])?,
+        depth: channel("depth", "Z")?,
+        position: vectors("position", ["X", "Y", "Z"])?,
+        material_id: ids("material_id")?,
+        object_id: ids("object_id")?,
+    };
+
+    Ok((img, aovs, channel("variance", "Y")?))
+}
+
+fn write_buffer(path: &str, aovs: &Aovs, color: impl Fn(usize) -> [f64; 3]) -> Result<(), RTError> {
+    let img_to_write = ImageBuffer::from_fn(aovs.width, aovs.height, |w, h| {
+        let [r, g, b] = color(aovs.index(w, aovs.height - 1 - h));
+        image::Rgb([
+            (256.0 * clamp(r, 0.0, 0.999)) as u8,
+            (256.0 * clamp(g, 0.0, 0.999)) as u8,
+            (256.0 * clamp(b, 0.0, 0.999)) as u8,
+        ])
+    });
+
+    img_to_write.save(path).map_err(RTError::ImageRS)
+}
+
+// Scatter the IDs over the hues so that neighbours can be told apart, 0 stays black
+fn id_color(id: u32) -> [f64; 3] {
+    if id == 0 {
+        return [0.0, 0.0, 0.0];
+    }
+    let hue = (id as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    match hue as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::Sphere, render_with_aovs, Lambertian, Material, Metal, RenderControl, RenderSettings,
+    };
+
+    #[test]
+    fn aovs_of_the_first_hit() {
+        let mut world = World::new(|_ray: &Ray| Color::new(0.5, 0.5, 0.5));
+        let red = Lambertian::new(Color::new(0.8, 0.1, 0.1));
+        world.add(Sphere::new_boxed(Vec3::new(0.0, 0.0, -3.0), 1.0, red));
+        world.add(Sphere::new_boxed(
+            Vec3::new(0.0, -101.0, -3.0),
+            100.0,
+            Metal::new(Color::new(0.5, 0.5, 0.5), 0.0),
+        ));
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+
+        let (_, mut aovs) = render_with_aovs(
+            Image::new(9, 9),
+            &world,
+            &camera,
+            &RenderSettings::new(4, 10),
+            &mut RenderControl::silent(),
+        );
+
+        // The center pixel looks at the front of the sphere
+        let center = aovs.index(4, 4);
+        assert!((aovs.albedo[center].vec - Vec3::new(0.8, 0.1, 0.1)).length() < 1e-9);
+        assert!((aovs.normal[center] - Vec3::new(0.0, 0.0, 1.0)).length() < 0.3);
+        assert!((aovs.depth[center] - 2.0).abs() < 0.05);
+        assert_eq!(aovs.object_id[center], 1);
+        assert_eq!(
+            aovs.material_id[center],
+            Lambertian::new(Color::new(0.8, 0.1, 0.1)).id()
+        );
+
+        // The bottom row sees the ground, the top one the sky
+        let ground = aovs.index(0, 0);
+        assert_eq!(aovs.object_id[ground], 2);
+        assert_ne!(aovs.material_id[ground], aovs.material_id[center]);
+        assert_ne!(aovs.material_id[ground], 0);
+        let sky = aovs.index(0, 8);
+        assert_eq!(aovs.object_id[sky], 0);
+        assert_eq!(aovs.material_id[sky], 0);
+        assert!(aovs.depth[sky].is_infinite());
+
+        // Every buffer makes a layer of the EXR file, the IDs keep all of their bits
+        aovs.material_id[sky] = (1 << 24) + 1;
+        let path = std::env::temp_dir().join(format!("ray-tracer-test-{}.exr", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut img = Image::new(9, 9);
+        for h in 0..9 {
+            for w in 0..9 {
+                img.add_pixel(w, h, Color::new(0.5, 0.5, 0.5));
+            }
+        }
+        write_layers_to_exr(path, &img, &aovs).unwrap();
+        let (read_img, read_aovs, variance) = read_layers_from_exr(path).unwrap();
+        assert_eq!(read_img.get_color_pixel(3, 2), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(read_aovs.material_id, aovs.material_id);
+        assert_eq!(read_aovs.object_id, aovs.object_id);
+        assert_eq!(read_aovs.depth[sky], f64::INFINITY);
+        assert_eq!(variance.len(), 81);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,6 +1,117 @@
-use crate::{math::Vec3, Ray};
+use crate::{
+    math::{Vec3, INFINITY, PI, TAU},
+    Hittable, Lens, RTError, Ray,
+};
 
+// How the fisheye lens spreads the angle `theta` from its axis over the distance `r` from the
+// center of the image: r ~ theta for equidistant lenses, r ~ sin(theta / 2) for equisolid ones
 #[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FisheyeMapping {
+    Equidistant,
+    Equisolid,
+}
+
+// Perspective cameras can have a thin lens, the other projections are always sharp.
+// The `fov` of a fisheye goes from one corner of the image to the opposite one, up to 360°
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+    Fisheye {
+        mapping: FisheyeMapping,
+        fov: f64,
+        aspect_ratio: f64,
+    },
+    Equirectangular,
+    // Equirectangular panorama seen by one eye turning its head: rays leave from a horizontal
+    // circle of radius |eye_offset|, on the right of the direction they go to when eye_offset > 0
+    OmnidirectionalStereo {
+        eye_offset: f64,
+    },
+}
+
+// Size of the film or of the sensor, in millimeters
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sensor {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Sensor {
+    pub const FULL_FRAME: Sensor = Sensor {
+        width: 36.0,
+        height: 24.0,
+    };
+    pub const APS_C: Sensor = Sensor {
+        width: 23.6,
+        height: 15.7,
+    };
+    pub const MICRO_FOUR_THIRDS: Sensor = Sensor {
+        width: 17.3,
+        height: 13.0,
+    };
+
+    pub fn new(width: f64, height: f64) -> Self {
+        Sensor { width, height }
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width / self.height
+    }
+
+    // How much smaller than a 35mm film the sensor is, comparing their diagonals
+    pub fn crop_factor(&self) -> f64 {
+        Sensor::FULL_FRAME.diagonal() / self.diagonal()
+    }
+
+    fn diagonal(&self) -> f64 {
+        (self.width * self.width + self.height * self.height).sqrt()
+    }
+
+    // Vertical field of view of a lens of `focal_length` millimeters in front of the sensor
+    pub fn vfov(&self, focal_length: f64) -> f64 {
+        2.0 * (self.height / (2.0 * focal_length)).atan()
+    }
+}
+
+// Vertical field of view of an image with `aspect_ratio` framed like with a lens of
+// `focal_length` millimeters on a 35mm film, the diagonal of the image matching the one of the film
+pub fn vfov_from_35mm_equivalent(focal_length: f64, aspect_ratio: f64) -> f64 {
+    let height = Sensor::FULL_FRAME.diagonal() / (aspect_ratio * aspect_ratio + 1.0).sqrt();
+    Sensor::new(aspect_ratio * height, height).vfov(focal_length)
+}
+
+// How long and how widely the camera opens and how sensitive its sensor is: `shutter_speed` in
+// seconds and `iso` the ISO speed of the sensor
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Exposure {
+    pub f_number: f64,
+    pub shutter_speed: f64,
+    pub iso: f64,
+}
+
+impl Exposure {
+    pub fn new(f_number: f64, shutter_speed: f64, iso: f64) -> Self {
+        Exposure {
+            f_number,
+            shutter_speed,
+            iso,
+        }
+    }
+
+    // Exposure value of the settings at ISO 100, the larger the less light gets in
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    // Factor from the radiance of the scene, in cd/m², to the value on the film, where 1 is the
+    // radiance that saturates the sensor (saturation based speed with a 78% middle gray)
+    pub fn scale(&self) -> f64 {
+        1.0 / (1.2 * 2f64.powf(self.ev100()))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Camera {
     pub origin: Vec3,
     pub lower_left_corner: Vec3,
@@ -13,6 +124,10 @@ pub struct Camera {
     pub aperture: f64,
     pub focus_dist: f64,
     pub vfov: f64,
+    pub projection: Projection,
+    pub lens: Lens,
+    // Without exposure the radiance goes to the film as it is
+    pub exposure: Option<Exposure>,
 }
 
 impl Camera {
@@ -52,18 +167,332 @@ impl Camera {
             aperture,
             focus_dist,
             vfov,
+            projection: Projection::Perspective,
+            lens: Lens::default(),
+            exposure: None,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::new_random_in_unit_disk();
-        let offset = self.u * rd.x + self.v * rd.y;
+    // A camera with a lens of `focal_length` millimeters in front of `sensor`, the opening of the
+    // lens coming from the f-number of `exposure`. The scene is measured in meters
+    pub fn new_physical(
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vup: Vec3,
+        focal_length: f64,
+        sensor: Sensor,
+        exposure: Exposure,
+        focus_dist: f64,
+    ) -> Camera {
+        let aperture = focal_length / exposure.f_number / 1000.0;
+        let mut camera = Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            sensor.vfov(focal_length),
+            sensor.aspect_ratio(),
+            aperture,
+            focus_dist,
+        );
+        camera.exposure = Some(exposure);
+        camera
+    }
+
+    // Factor applied to the radiance reaching the film
+    pub fn film_scale(&self) -> f64 {
+        self.exposure.map_or(1.0, |exposure| exposure.scale())
+    }
+
+    // Parallel rays leaving a `height` high rectangle centered on `lookfrom`
+    pub fn new_orthographic(
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vup: Vec3,
+        height: f64,
+        aspect_ratio: f64,
+    ) -> Camera {
+        let mut camera = Camera::new(lookfrom, lookat, vup, 0.0, aspect_ratio, 0.0, 1.0);
+        camera.horizontal = aspect_ratio * height * camera.u;
+        camera.vertical = height * camera.v;
+        camera.lower_left_corner = camera.origin - camera.horizontal / 2.0 - camera.vertical / 2.0;
+        camera.projection = Projection::Orthographic;
+        camera
+    }
+
+    pub fn new_fisheye(
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vup: Vec3,
+        fov: f64,
+        aspect_ratio: f64,
+        mapping: FisheyeMapping,
+    ) -> Camera {
+        let mut camera = Camera::new(lookfrom, lookat, vup, fov, aspect_ratio, 0.0, 1.0);
+        camera.projection = Projection::Fisheye {
+            mapping,
+            fov,
+            aspect_ratio,
+        };
+        camera
+    }
+
+    // The whole sphere around `lookfrom`, `lookat` being in the middle of the image. The image
+    // should be twice as wide as high
+    pub fn new_equirectangular(lookfrom: Vec3, lookat: Vec3, vup: Vec3) -> Camera {
+        let mut camera = Camera::new(lookfrom, lookat, vup, PI, 2.0, 0.0, 1.0);
+        camera.projection = Projection::Equirectangular;
+        camera
+    }
+
+    // None when the barrel of the lens stops the ray, see `Lens::cat_eye`: the sample is black
+    pub fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_magnified_ray(s, t, 1.0)
+    }
+
+    // Ray for one channel only (0 for red, 1 for green, 2 for blue), they differ with lateral
+    // chromatic aberration
+    pub fn get_channel_ray(&self, s: f64, t: f64, channel: usize) -> Option<Ray> {
+        self.get_magnified_ray(s, t, self.lens.channel_magnification(channel))
+    }
+
+    fn get_magnified_ray(&self, s: f64, t: f64, magnification: f64) -> Option<Ray> {
+        let (x, y) = self.centered(s, t);
+        let (s, t) = self.uncentered(self.lens.distort(x, y, magnification));
+        if self.projection != Projection::Perspective || self.lens_radius == 0.0 {
+            return Some(self.project(s, t));
+        }
+
+        let (lens_x, lens_y) = self.lens.sample_aperture(x, y)?;
+        let offset = self.lens_radius * (lens_x * self.u + lens_y * self.v);
 
-        Ray::new(
+        Some(Ray::new(
             self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            self.focus_point(s, t) - self.origin - offset,
+        ))
+    }
+
+    // Point in focus seen at (s, t), on the plane at the focus distance unless the lens tilts it
+    fn focus_point(&self, s: f64, t: f64) -> Vec3 {
+        let target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        if self.lens.tilt == (0.0, 0.0) {
+            return target;
+        }
+
+        let (tilt, swing) = self.lens.tilt;
+        let normal =
+            tilt.cos() * (swing.cos() * self.w + swing.sin() * self.u) + tilt.sin() * self.v;
+        let direction = target - self.origin;
+        // The plane still goes through the point in focus at the center of the image
+        let k = -self.focus_dist * Vec3::dot(&self.w, &normal) / Vec3::dot(&direction, &normal);
+        if k > 0.0 && k.is_finite() {
+            self.origin + k * direction
+        } else {
+            target
+        }
+    }
+
+    // Moves the plane in focus to `focus_dist` from the camera, keeping the framing. The plane must
+    // be in front of the camera
+    pub fn set_focus_dist(&mut self, focus_dist: f64) -> Result<(), RTError> {
+        if !(focus_dist > 0.0 && focus_dist.is_finite()) {
+            return Err(RTError::InvalidArgument(format!(
+                "focus distance {}",
+                focus_dist
+            )));
+        }
+        if self.projection == Projection::Perspective {
+            let scale = focus_dist / self.focus_dist;
+            self.horizontal = scale * self.horizontal;
+            self.vertical = scale * self.vertical;
+            self.lower_left_corner = self.origin + scale * (self.lower_left_corner - self.origin);
+        }
+        self.focus_dist = focus_dist;
+        Ok(())
+    }
+
+    // Focuses on `point` and returns the new focus distance, or None when the point is not in front
+    // of the camera and the camera is left as it was
+    pub fn focus_on(&mut self, point: Vec3) -> Option<f64> {
+        self.set_focus_dist(Vec3::dot(&(self.origin - point), &self.w))
+            .ok()?;
+        Some(self.focus_dist)
+    }
+
+    // Focuses on what is seen at (s, t) in the image and returns the new focus distance, or None
+    // when the ray goes to the background and the camera is left as it was
+    pub fn autofocus(&mut self, scene: &dyn Hittable, s: f64, t: f64) -> Option<f64> {
+        let ray = self.get_pinhole_ray(s, t);
+        let point = scene.hit(&ray, 0.001, INFINITY)?.point;
+        self.focus_on(point)
+    }
+
+    // Same ray as `get_ray` but always from the center of the lens, so without any randomness
+    pub fn get_pinhole_ray(&self, s: f64, t: f64) -> Ray {
+        let (x, y) = self.centered(s, t);
+        let (s, t) = self.uncentered(self.lens.distort(x, y, 1.0));
+        self.project(s, t)
+    }
+
+    // Position on the image relative to its center, the corners being at a distance of 1
+    fn centered(&self, s: f64, t: f64) -> (f64, f64) {
+        let aspect_ratio = self.aspect_ratio();
+        let diagonal = (aspect_ratio.powi(2) + 1.0).sqrt();
+        (
+            (2.0 * s - 1.0) * aspect_ratio / diagonal,
+            (2.0 * t - 1.0) / diagonal,
         )
     }
+
+    fn uncentered(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let aspect_ratio = self.aspect_ratio();
+        let diagonal = (aspect_ratio.powi(2) + 1.0).sqrt();
+        (
+            0.5 + 0.5 * x * diagonal / aspect_ratio,
+            0.5 + 0.5 * y * diagonal,
+        )
+    }
+
+    fn aspect_ratio(&self) -> f64 {
+        match self.projection {
+            Projection::Fisheye { aspect_ratio, .. } => aspect_ratio,
+            Projection::Equirectangular | Projection::OmnidirectionalStereo { .. } => 2.0,
+            Projection::Perspective | Projection::Orthographic => {
+                self.horizontal.length() / self.vertical.length()
+            }
+        }
+    }
+
+    fn project(&self, s: f64, t: f64) -> Ray {
+        match self.projection {
+            Projection::Perspective => Ray::new(
+                self.origin,
+                self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+            ),
+            Projection::Orthographic => Ray::new(
+                self.lower_left_corner + s * self.horizontal + t * self.vertical,
+                -self.w,
+            ),
+            Projection::Fisheye { mapping, fov, .. } => {
+                let (x, y) = self.centered(s, t);
+                let r = (x * x + y * y).sqrt();
+
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * fov / 2.0,
+                    FisheyeMapping::Equisolid => 2.0 * (r * (fov / 4.0).sin()).min(1.0).asin(),
+                };
+                let phi = y.atan2(x);
+
+                Ray::new(
+                    self.origin,
+                    theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) - theta.cos() * self.w,
+                )
+            }
+            Projection::Equirectangular => Ray::new(self.origin, self.panorama_direction(s, t)),
+            Projection::OmnidirectionalStereo { eye_offset } => {
+                let longitude = (s - 0.5) * TAU;
+                let right = longitude.cos() * self.u + longitude.sin() * self.w;
+                Ray::new(
+                    self.origin + eye_offset * right,
+                    self.panorama_direction(s, t),
+                )
+            }
+        }
+    }
+
+    fn panorama_direction(&self, s: f64, t: f64) -> Vec3 {
+        let longitude = (s - 0.5) * TAU;
+        let latitude = (t - 0.5) * PI;
+        latitude.cos() * (longitude.sin() * self.u - longitude.cos() * self.w)
+            + latitude.sin() * self.v
+    }
+
+    // The same camera moved to `lookfrom` and turned toward `lookat`, keeping the up direction
+    pub fn looking_at(&self, lookfrom: Vec3, lookat: Vec3) -> Camera {
+        let w = Vec3::unit(lookfrom - lookat);
+        let u = Vec3::unit(Vec3::cross(&self.v, &w));
+        let v = Vec3::cross(&w, &u);
+
+        // Same position of the corner relative to the camera, in the new basis
+        let corner = self.lower_left_corner - self.origin;
+        let lower_left_corner = lookfrom
+            + Vec3::dot(&corner, &self.u) * u
+            + Vec3::dot(&corner, &self.v) * v
+            + Vec3::dot(&corner, &self.w) * w;
+
+        Camera {
+            origin: lookfrom,
+            lower_left_corner,
+            horizontal: self.horizontal.length() * u,
+            vertical: self.vertical.length() * v,
+            u,
+            v,
+            w,
+            ..self.clone()
+        }
+    }
+}
+
+// How the eyes of a stereo camera look at the scene: both straight ahead, or turned toward the
+// point at the focus distance of the camera, where the two images then match
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Convergence {
+    Parallel,
+    ToedIn,
+}
+
+// Where the image of each eye goes in the rendered image: left eye on the left or on the top
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StereoLayout {
+    SideBySide,
+    OverUnder,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StereoCamera {
+    pub left: Camera,
+    pub right: Camera,
+    pub layout: StereoLayout,
+}
+
+impl StereoCamera {
+    // Two eyes `interocular` apart, on each side of `camera`
+    pub fn new(
+        camera: &Camera,
+        interocular: f64,
+        convergence: Convergence,
+        layout: StereoLayout,
+    ) -> Self {
+        let eye = |side: f64| {
+            let lookfrom = camera.origin + side * interocular / 2.0 * camera.u;
+            let lookat = match convergence {
+                Convergence::Parallel => lookfrom - camera.w,
+                Convergence::ToedIn => camera.origin - camera.focus_dist * camera.w,
+            };
+            camera.looking_at(lookfrom, lookat)
+        };
+
+        StereoCamera {
+            left: eye(-1.0),
+            right: eye(1.0),
+            layout,
+        }
+    }
+
+    // Omnidirectional stereo: a 360° panorama for each eye, one over the other. The image should
+    // be as wide as high
+    pub fn new_omnidirectional(lookfrom: Vec3, lookat: Vec3, vup: Vec3, interocular: f64) -> Self {
+        let eye = |eye_offset: f64| {
+            let mut camera = Camera::new_equirectangular(lookfrom, lookat, vup);
+            camera.projection = Projection::OmnidirectionalStereo { eye_offset };
+            camera
+        };
+
+        StereoCamera {
+            left: eye(-interocular / 2.0),
+            right: eye(interocular / 2.0),
+            layout: StereoLayout::OverUnder,
+        }
+    }
 }
 
 // #[test]
@@ -105,3 +534,152 @@ impl Camera {
 //         camera.lower_left_corner + u * camera.horizontal + v * camera.vertical - camera.origin
 //     );
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Sphere, Color, Lambertian, World};
+
+    fn angle(a: &Vec3, b: &Vec3) -> f64 {
+        (Vec3::dot(a, b) / (a.length() * b.length())).acos()
+    }
+
+    #[test]
+    fn projections_keep_the_framing() {
+        let lookfrom = Vec3::new(1.0, 2.0, 3.0);
+        let lookat = Vec3::new(1.0, 2.0, -1.0);
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+        let right = Vec3::new(1.0, 0.0, 0.0);
+
+        let ortho = Camera::new_orthographic(lookfrom, lookat, vup, 2.0, 1.5);
+        let corner = ortho.get_ray(0.0, 0.0).unwrap();
+        assert_eq!(Vec3::unit(corner.direction), forward);
+        assert!((corner.origin - Vec3::new(-0.5, 1.0, 3.0)).length() < 1e-9);
+
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid].iter() {
+            let fisheye = Camera::new_fisheye(lookfrom, lookat, vup, 1.5 * PI, 1.5, *mapping);
+            assert!(angle(&fisheye.get_ray(0.5, 0.5).unwrap().direction, &forward) < 1e-9);
+            let corner = fisheye.get_ray(1.0, 1.0).unwrap().direction;
+            assert!((angle(&corner, &forward) - 0.75 * PI).abs() < 1e-9);
+            assert!(corner.x > 0.0 && corner.y > 0.0);
+        }
+
+        let panorama = Camera::new_equirectangular(lookfrom, lookat, vup);
+        let ods = StereoCamera::new_omnidirectional(lookfrom, lookat, vup, 0.064);
+        let backward = ods.left.get_ray(0.0, 0.5).unwrap();
+        assert!(angle(&backward.direction, &-forward) < 1e-9);
+        assert!((backward.origin - (lookfrom + 0.032 * right)).length() < 1e-9);
+        assert!(
+            (ods.right.get_ray(0.5, 0.5).unwrap().origin - (lookfrom + 0.032 * right)).length()
+                < 1e-9
+        );
+        assert!(angle(&panorama.get_ray(0.5, 0.5).unwrap().direction, &forward) < 1e-9);
+        assert!(angle(&panorama.get_ray(0.75, 0.5).unwrap().direction, &right) < 1e-9);
+        assert!(angle(&panorama.get_ray(0.0, 0.5).unwrap().direction, &-forward) < 1e-9);
+        assert!(angle(&panorama.get_ray(0.3, 1.0).unwrap().direction, &vup) < 1e-9);
+    }
+
+    #[test]
+    fn physical_camera() {
+        // Sunny 16 rule: f/16 at 1/100s and ISO 100 in the sun
+        let sunny = Exposure::new(16.0, 1.0 / 100.0, 100.0);
+        assert!((sunny.ev100() - 14.64).abs() < 0.01);
+        // One stop more light, twice the value on the film
+        let open = Exposure::new(16.0 / 2f64.sqrt(), 1.0 / 100.0, 100.0);
+        assert!((open.scale() / sunny.scale() - 2.0).abs() < 1e-9);
+        assert!(
+            (Exposure::new(16.0, 1.0 / 100.0, 200.0).ev100() - (sunny.ev100() - 1.0)).abs() < 1e-9
+        );
+
+        // A 50mm lens on a full frame sees 27° vertically, on the smaller APS-C like a 75mm
+        assert!((Sensor::FULL_FRAME.vfov(50.0).to_degrees() - 26.99).abs() < 0.01);
+        assert!((Sensor::APS_C.crop_factor() - 1.53).abs() < 0.01);
+        let equivalent = vfov_from_35mm_equivalent(50.0 * Sensor::APS_C.crop_factor(), 1.5);
+        assert!((Sensor::APS_C.vfov(50.0) - equivalent).abs() < 1e-3);
+
+        let camera = Camera::new_physical(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            50.0,
+            Sensor::FULL_FRAME,
+            Exposure::new(2.0, 1.0 / 100.0, 100.0),
+            3.0,
+        );
+        assert!((camera.aperture - 0.025).abs() < 1e-9);
+        assert!((camera.vfov - Sensor::FULL_FRAME.vfov(50.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn focusing() {
+        let mut world = World::new(|_: &Ray| Color::new(0.0, 0.0, 0.0));
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        world.add(Sphere::new_boxed(Vec3::new(0.0, 0.0, -5.0), 1.0, material));
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.5,
+            0.1,
+            10.0,
+        );
+        let corner = camera.get_pinhole_ray(0.0, 0.0).direction;
+
+        assert_eq!(camera.autofocus(&world, 0.0, 0.0), None);
+        assert!((camera.autofocus(&world, 0.5, 0.5).unwrap() - 4.0).abs() < 1e-9);
+        // Same framing at the new distance
+        let refocused = camera.get_pinhole_ray(0.0, 0.0).direction;
+        assert!(Vec3::cross(&corner, &refocused).length() < 1e-9);
+        assert!((camera.focus_point(0.5, 0.5) - Vec3::new(0.0, 0.0, -4.0)).length() < 1e-9);
+
+        camera.lens.tilt = (0.2, 0.0);
+        assert!((camera.focus_point(0.5, 0.5) - Vec3::new(0.0, 0.0, -4.0)).length() < 1e-9);
+        assert!(camera.focus_point(0.5, 1.0).z < -4.0 && camera.focus_point(0.5, 0.0).z > -4.0);
+
+        // Nothing behind the camera or in its plane can be in focus
+        assert_eq!(camera.focus_on(Vec3::new(0.0, 0.0, 1.0)), None);
+        assert_eq!(camera.focus_on(Vec3::new(1.0, 0.0, 0.0)), None);
+        assert!(camera.set_focus_dist(f64::NAN).is_err());
+        assert!((camera.focus_dist - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stereo_eyes() {
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.5,
+            0.0,
+            2.0,
+        );
+
+        let parallel = StereoCamera::new(
+            &camera,
+            0.1,
+            Convergence::Parallel,
+            StereoLayout::SideBySide,
+        );
+        assert_eq!(parallel.left.origin, Vec3::new(-0.05, 0.0, 0.0));
+        assert_eq!(parallel.right.origin, Vec3::new(0.05, 0.0, 0.0));
+        assert!(
+            (parallel.left.get_ray(0.5, 0.5).unwrap().direction
+                - camera.get_ray(0.5, 0.5).unwrap().direction)
+                .length()
+                < 1e-9
+        );
+
+        // Both eyes look at the point at the focus distance
+        let toed_in =
+            StereoCamera::new(&camera, 0.1, Convergence::ToedIn, StereoLayout::SideBySide);
+        for eye in [toed_in.left, toed_in.right].iter() {
+            let ray = eye.get_ray(0.5, 0.5).unwrap();
+            let target = Vec3::new(0.0, 0.0, -2.0) - ray.origin;
+            assert!(angle(&ray.direction, &target) < 1e-9);
+            assert!((eye.horizontal.length() - camera.horizontal.length()).abs() < 1e-9);
+        }
+    }
+}
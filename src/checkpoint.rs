@@ -0,0 +1,426 @@
+use crate::{Camera, Color, Hittable, Image, PixelStats, RTError, Ray, RenderSettings, World};
+use std::{convert::TryInto, fmt::Debug, fs, hash::Hasher, time::Duration};
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 2;
+// Sum, weight and stats of a pixel: 4 f64, a u32 and 2 f64
+const PIXEL_SIZE: usize = 52;
+
+// FNV-1a, unlike the std hasher its output is guaranteed to stay the same from one build to another
+pub struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher {
+            state: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+}
+
+impl StableHasher {
+    // Hash a value by its debug representation, which holds every field and prints the floats
+    // exactly
+    pub fn write_debug(&mut self, value: &impl Debug) {
+        self.write(format!("{:?}", value).as_bytes());
+        self.write_u8(0);
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+// Identifies what is rendered: the objects, lights and materials of the world, its background and
+// the camera. Renders can only be merged if they share the same one, see `Hittable::fingerprint`
+pub fn scene_hash<F>(world: &World<F>, camera: &Camera) -> u64
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut hasher = StableHasher::new();
+    hasher.write_debug(camera);
+    world.fingerprint(&mut hasher);
+    hasher.finish()
+}
+
+// Identifies a render: its scene hash, the size of the image and the settings.
+// A checkpoint can only be resumed with the same one
+pub fn settings_hash(img: &Image, scene_hash: u64, settings: &RenderSettings) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write_u32(img.width);
+    hasher.write_u32(img.height);
+    hasher.write_u64(scene_hash);
+    hasher.write(format!("{:?}", settings).as_bytes());
+    hasher.finish()
+}
+
+// Everything needed to carry on a render: the accumulation buffers of the image, the state of the
+// random generator used for the samples positions and the hash of what is rendered
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub scene_hash: u64,
+    pub settings_hash: u64,
+    pub rng_state: u64,
+    pub img: Image,
+}
+
+impl Checkpoint {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let img = &self.img;
+        let mut bytes = Vec::with_capacity(40 + (img.width * img.height) as usize * PIXEL_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.scene_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.settings_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.rng_state.to_le_bytes());
+        bytes.extend_from_slice(&img.width.to_le_bytes());
+        bytes.extend_from_slice(&img.height.to_le_bytes());
+
+        for h in 0..img.height {
+            for w in 0..img.width {
+                let sum = img
+                    .pixels
+                    .get(&(w, h))
+                    .copied()
+                    .unwrap_or(Color::new(0.0, 0.0, 0.0));
+                let weight = img.weights.get(&(w, h)).copied().unwrap_or(0.0);
+                let stats = img.get_pixel_stats(w, h);
+
+                for value in [sum.r(), sum.g(), sum.b(), weight].iter() {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                bytes.extend_from_slice(&stats.count.to_le_bytes());
+                bytes.extend_from_slice(&stats.mean.to_le_bytes());
+                bytes.extend_from_slice(&stats.m2.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RTError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(RTError::InvalidCheckpoint(
+                "not a checkpoint file".to_string(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(RTError::InvalidCheckpoint(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let scene_hash = reader.u64()?;
+        let settings_hash = reader.u64()?;
+        let rng_state = reader.u64()?;
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        // Checked before making the image, a corrupt size could not be allocated
+        let size = width
+            .checked_mul(height)
+            .and_then(|pixels| (pixels as usize).checked_mul(PIXEL_SIZE));
+        if size != Some(bytes.len() - reader.position) {
+            return Err(RTError::InvalidCheckpoint(format!(
+                "{}x{} pixels do not match the size of the file",
+                width, height
+            )));
+        }
+
+        let mut img = Image::new(width, height);
+        for h in 0..height {
+            for w in 0..width {
+                let sum = Color::new(reader.f64()?, reader.f64()?, reader.f64()?);
+                let weight = reader.f64()?;
+                let stats = PixelStats {
+                    count: reader.u32()?,
+                    mean: reader.f64()?,
+                    m2: reader.f64()?,
+                };
+
+                if weight != 0.0 {
+                    img.pixels.insert((w, h), sum);
+                    img.weights.insert((w, h), weight);
+                }
+                if stats.count != 0 {
+                    img.stats.insert((w, h), stats);
+                }
+            }
+        }
+
+        Ok(Checkpoint {
+            scene_hash,
+            settings_hash,
+            rng_state,
+            img,
+        })
+    }
+
+    // Written next to `path` first then renamed, a render killed while saving keeps the previous one
+    pub fn save(&self, path: &str) -> Result<(), RTError> {
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, self.to_bytes()).map_err(RTError::IO)?;
+        fs::rename(&tmp_path, path).map_err(RTError::IO)
+    }
+
+    pub fn load(path: &str) -> Result<Self, RTError> {
+        Self::from_bytes(&fs::read(path).map_err(RTError::IO)?)
+    }
+
+    // Combine renders of the same scene made independently (on other machines, with other seeds...).
+    // The sums of every pixel are added, so each render counts as much as the samples it took and
+    // the result is the same as a single render with all the samples. The settings and the random
+    // state of the first render are kept
+    pub fn merge(checkpoints: &[Checkpoint]) -> Result<Checkpoint, RTError> {
+        let (first, others) = checkpoints
+            .split_first()
+            .ok_or_else(|| RTError::MergeMismatch("nothing to merge".to_string()))?;
+
+        let mut img = first.img.clone();
+        for other in others {
+            if other.scene_hash != first.scene_hash {
+                return Err(RTError::MergeMismatch(format!(
+                    "scene hash {:016x} instead of {:016x}",
+                    other.scene_hash, first.scene_hash
+                )));
+            }
+            if (other.img.width, other.img.height) != (img.width, img.height) {
+                return Err(RTError::MergeMismatch(format!(
+                    "size {}x{} instead of {}x{}",
+                    other.img.width, other.img.height, img.width, img.height
+                )));
+            }
+            img.merge(&other.img);
+        }
+
+        Ok(Checkpoint {
+            scene_hash: first.scene_hash,
+            settings_hash: first.settings_hash,
+            rng_state: first.rng_state,
+            img,
+        })
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], RTError> {
+        let end = self.position + n;
+        if end > self.bytes.len() {
+            return Err(RTError::InvalidCheckpoint("truncated file".to_string()));
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, RTError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, RTError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, RTError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checkpointing {
+    pub path: String,
+    pub every: Duration,
+}
+
+impl Checkpointing {
+    pub fn new(path: &str, every: Duration) -> Self {
+        Checkpointing {
+            path: path.to_string(),
+            every,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{Sphere, Vec3},
+        render_with_checkpoints, Lambertian, Metal, RenderControl,
+    };
+    use std::env;
+
+    fn gray_world() -> World<impl Fn(&Ray) -> Color> {
+        World::new(|_ray: &Ray| Color::new(0.5, 0.5, 0.5))
+    }
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let mut img = Image::new(3, 2);
+        img.splat(0, 0, Color::new(0.1, 0.2, 0.3), 0.5);
+        img.add_sample_stats(0, 0, &Color::new(0.1, 0.2, 0.3));
+        img.add_sample_stats(0, 0, &Color::new(0.4, 0.2, 0.3));
+        img.add_pixel(2, 1, Color::new(1.0, 1.0, 1.0));
+        let checkpoint = Checkpoint {
+            scene_hash: 4321,
+            settings_hash: 1234,
+            rng_state: 5678,
+            img,
+        };
+
+        let read = Checkpoint::from_bytes(&checkpoint.to_bytes()).unwrap();
+        assert_eq!(read, checkpoint);
+
+        let bytes = checkpoint.to_bytes();
+        assert!(Checkpoint::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        // A corrupt size is refused before anything is allocated
+        let mut huge = bytes[..40].to_vec();
+        huge[32..40].copy_from_slice(&[0xff; 8]);
+        assert!(matches!(
+            Checkpoint::from_bytes(&huge),
+            Err(RTError::InvalidCheckpoint(_))
+        ));
+    }
+
+    #[test]
+    fn resume_needs_same_settings() {
+        let path = env::temp_dir().join(format!("ray-tracer-test-{}.ckpt", std::process::id()));
+        let checkpointing = Checkpointing::new(path.to_str().unwrap(), Duration::from_secs(60));
+        let world = gray_world();
+        let settings = RenderSettings::new(4, 10);
+
+        let img = render_with_checkpoints(
+            Image::new(4, 3),
+            &world,
+            &camera(),
+            &settings,
+            &checkpointing,
+            false,
+            &mut RenderControl::silent(),
+        )
+        .unwrap();
+
+        // Resuming a finished render gives it back as it was
+        let resumed = render_with_checkpoints(
+            Image::new(4, 3),
+            &world,
+            &camera(),
+            &settings,
+            &checkpointing,
+            true,
+            &mut RenderControl::silent(),
+        )
+        .unwrap();
+        assert_eq!(resumed, img);
+
+        let result = render_with_checkpoints(
+            Image::new(4, 3),
+            &world,
+            &camera(),
+            &RenderSettings::new(8, 10),
+            &checkpointing,
+            true,
+            &mut RenderControl::silent(),
+        );
+        assert!(matches!(result, Err(RTError::CheckpointMismatch { .. })));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scene_hash_sees_every_object() {
+        let scene = |x: f64, fuzz: f64| {
+            let mut world = gray_world();
+            world.add(Sphere::new_boxed(
+                Vec3::new(0.0, 0.0, -5.0),
+                1.0,
+                Lambertian::new(Color::new(0.5, 0.5, 0.5)),
+            ));
+            // Far too small to be hit by rays traced through the image
+            world.add(Sphere::new_boxed(
+                Vec3::new(x, 0.0, -5.0),
+                1e-6,
+                Metal::new(Color::new(0.5, 0.5, 0.5), fuzz),
+            ));
+            scene_hash(&world, &camera())
+        };
+        assert_eq!(scene(2.0, 0.1), scene(2.0, 0.1));
+        assert_ne!(scene(2.0, 0.1), scene(2.001, 0.1));
+        assert_ne!(scene(2.0, 0.1), scene(2.0, 0.2));
+
+        let dark = World::new(|_ray: &Ray| Color::new(0.0, 0.0, 0.0));
+        assert_ne!(
+            scene_hash(&dark, &camera()),
+            scene_hash(&gray_world(), &camera())
+        );
+    }
+
+    #[test]
+    fn merge_adds_samples() {
+        let world = gray_world();
+        let camera = camera();
+        let hash = scene_hash(&world, &camera);
+        let render = |samples| {
+            let settings = RenderSettings::new(samples, 10);
+            let img = crate::render(Image::new(4, 3), &world, &camera, &settings);
+            Checkpoint {
+                scene_hash: hash,
+                settings_hash: settings_hash(&img, hash, &settings),
+                rng_state: 0,
+                img,
+            }
+        };
+
+        let merged = Checkpoint::merge(&[render(3), render(5)]).unwrap();
+        for h in 0..3 {
+            for w in 0..4 {
+                assert_eq!(merged.img.get_pixel_stats(w, h).count, 8);
+                let color = merged.img.get_color_pixel(w, h);
+                assert!((color.vec - Vec3::new(0.5, 0.5, 0.5)).length() < 1e-9);
+            }
+        }
+
+        let mut other_scene = render(2);
+        other_scene.scene_hash += 1;
+        assert!(Checkpoint::merge(&[render(3), other_scene]).is_err());
+
+        let mut other_size = render(2);
+        other_size.img = Image::new(3, 3);
+        assert!(Checkpoint::merge(&[render(3), other_size]).is_err());
+    }
+}
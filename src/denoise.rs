@@ -0,0 +1,253 @@
+use crate::{math::Vec3, Aovs, Color, Image, RTError};
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010) with the luminance weight driven by
+// the variance of the pixels (Schied et al. 2017). Each iteration blurs with a 5x5 kernel whose
+// taps are twice as spread out as in the previous one, neighbours only counting as much as they
+// look like the pixel: same normal (`sigma_normal` is the exponent of their cosine), same relative
+// depth, same albedo and a luminance within `sigma_luminance` standard deviations
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Denoiser {
+    pub iterations: u32,
+    pub sigma_luminance: f64,
+    pub sigma_normal: f64,
+    pub sigma_depth: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_luminance: 4.0,
+            sigma_normal: 128.0,
+            sigma_depth: 0.05,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Denoiser::default()
+    }
+
+    // `variance` is the variance of the luminance of each pixel, see `variance_buffer`. The buffers
+    // must have one value per pixel of `img`. The stats of the samples of `img` are kept as they are
+    pub fn denoise(&self, img: &Image, aovs: &Aovs, variance: &[f64]) -> Result<Image, RTError> {
+        check_sizes(img, aovs, variance)?;
+        let (width, height) = (img.width as i64, img.height as i64);
+        let mut colors: Vec<Color> = (0..img.height)
+            .flat_map(|h| (0..img.width).map(move |w| (w, h)))
+            .map(|(w, h)| img.get_color_pixel(w, h))
+            .collect();
+        let mut variance = variance.to_vec();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let mut filtered_colors = colors.clone();
+            let mut filtered_variance = variance.clone();
+
+            for h in 0..height {
+                for w in 0..width {
+                    let p = (h * width + w) as usize;
+                    let luminance_p = colors[p].luminance();
+                    // Estimated from a few samples the variance is noisy too, it is blurred a bit
+                    let variance_p = blurred_variance(&variance, width, height, w, h);
+                    let luminance_scale = self.sigma_luminance * variance_p.max(0.0).sqrt() + 1e-6;
+
+                    let mut sum_color = Vec3::new(0.0, 0.0, 0.0);
+                    let mut sum_variance = 0.0;
+                    let mut sum_weight = 0.0;
+                    for (j, kernel_y) in KERNEL.iter().enumerate() {
+                        for (i, kernel_x) in KERNEL.iter().enumerate() {
+                            let x = w + (i as i64 - 2) * step;
+                            let y = h + (j as i64 - 2) * step;
+                            if x < 0 || y < 0 || x >= width || y >= height {
+                                continue;
+                            }
+                            let q = (y * width + x) as usize;
+
+                            let weight_luminance = (-(colors[q].luminance() - luminance_p).abs()
+                                / luminance_scale)
+                                .exp();
+                            let weight = kernel_x
+                                * kernel_y
+                                * weight_luminance
+                                * self.feature_weight(aovs, p, q, step as f64);
+
+                            sum_color += weight * colors[q].vec;
+                            sum_variance += weight * weight * variance[q];
+                            sum_weight += weight;
+                        }
+                    }
+
+                    // The pixel itself always has a weight, the sum can not be 0
+                    filtered_colors[p] = Color::new_with_vec(sum_color / sum_weight);
+                    filtered_variance[p] = sum_variance / (sum_weight * sum_weight);
+                }
+            }
+
+            colors = filtered_colors;
+            variance = filtered_variance;
+        }
+
+        let mut denoised = img.clone();
+        for h in 0..img.height {
+            for w in 0..img.width {
+                denoised.add_pixel(w, h, colors[(h * img.width + w) as usize]);
+            }
+        }
+        Ok(denoised)
+    }
+
+    // How much the surface seen by the pixel `q` looks like the one seen by `p`
+    fn feature_weight(&self, aovs: &Aovs, p: usize, q: usize, step: f64) -> f64 {
+        let (depth_p, depth_q) = (aovs.depth[p], aovs.depth[q]);
+        if depth_p.is_infinite() || depth_q.is_infinite() {
+            // Pixels of the background only mix together
+            return if depth_p.is_infinite() && depth_q.is_infinite() {
+                1.0
+            } else {
+                0.0
+            };
+        }
+
+        let weight_normal = Vec3::dot(&aovs.normal[p], &aovs.normal[q])
+            .max(0.0)
+            .powf(self.sigma_normal);
+        let weight_depth =
+            (-(depth_p - depth_q).abs() / (self.sigma_depth * step * depth_p.abs() + 1e-6)).exp();
+        let albedo_distance = (aovs.albedo[p].vec - aovs.albedo[q].vec).length_squared();
+        let weight_albedo = (-albedo_distance / self.sigma_albedo.powi(2)).exp();
+
+        weight_normal * weight_depth * weight_albedo
+    }
+}
+
+fn check_sizes(img: &Image, aovs: &Aovs, variance: &[f64]) -> Result<(), RTError> {
+    let size = (img.width * img.height) as usize;
+    let buffers = [
+        ("albedo", aovs.albedo.len()),
+        ("normal", aovs.normal.len()),
+        ("depth", aovs.depth.len()),
+        ("variance", variance.len()),
+    ];
+    if (aovs.width, aovs.height) != (img.width, img.height) {
+        return Err(RTError::InvalidArgument(format!(
+            "the buffers are {}x{} for an image of {}x{}",
+            aovs.width, aovs.height, img.width, img.height
+        )));
+    }
+    if let Some((name, len)) = buffers.iter().find(|(_, len)| *len != size) {
+        return Err(RTError::InvalidArgument(format!(
+            "the {} buffer has {} values for {} pixels",
+            name, len, size
+        )));
+    }
+    Ok(())
+}
+
+// 3x3 gaussian blur of the variance around (w, h)
+fn blurred_variance(variance: &[f64], width: i64, height: i64, w: i64, h: i64) -> f64 {
+    const GAUSSIAN: [f64; 3] = [0.25, 0.5, 0.25];
+    let mut sum = 0.0;
+    let mut sum_weight = 0.0;
+    for (j, weight_y) in GAUSSIAN.iter().enumerate() {
+        for (i, weight_x) in GAUSSIAN.iter().enumerate() {
+            let x = w + i as i64 - 1;
+            let y = h + j as i64 - 1;
+            if x >= 0 && y >= 0 && x < width && y < height {
+                sum += weight_x * weight_y * variance[(y * width + x) as usize];
+                sum_weight += weight_x * weight_y;
+            }
+        }
+    }
+    sum / sum_weight
+}
+
+// Variance of the mean luminance of each pixel, estimated from its samples and stored row after
+// row from the bottom like `Aovs`. Pixels with less than 2 samples get 0
+pub fn variance_buffer(img: &Image) -> Vec<f64> {
+    (0..img.height)
+        .flat_map(|h| (0..img.width).map(move |w| (w, h)))
+        .map(|(w, h)| {
+            let stats = img.get_pixel_stats(w, h);
+            if stats.count < 2 {
+                0.0
+            } else {
+                stats.variance() / stats.count as f64
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    // Dark left half facing the camera and light right half looking up, both with noise
+    fn noisy_scene() -> (Image, Aovs, Vec<f64>) {
+        let mut rng = rand::thread_rng();
+        let (width, height) = (32, 16);
+        let mut img = Image::new(width, height);
+        let mut aovs = Aovs::new(width, height);
+
+        for h in 0..height {
+            for w in 0..width {
+                let i = aovs.index(w, h);
+                let gray = if w < width / 2 { 0.2 } else { 0.8 };
+                for _ in 0..4 {
+                    let noise = rng.gen_range(-0.1..0.1);
+                    let sample = Color::new(gray + noise, gray + noise, gray + noise);
+                    img.add_sample_stats(w, h, &sample);
+                    img.splat(w, h, sample, 1.0);
+                }
+                aovs.albedo[i] = Color::new(0.5, 0.5, 0.5);
+                aovs.normal[i] = if w < width / 2 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                };
+                aovs.depth[i] = 5.0;
+            }
+        }
+
+        let variance = variance_buffer(&img);
+        (img, aovs, variance)
+    }
+
+    #[test]
+    fn denoising_removes_noise_but_keeps_edges() {
+        let (img, aovs, variance) = noisy_scene();
+        let denoised = Denoiser::new().denoise(&img, &aovs, &variance).unwrap();
+
+        // Root mean square error, and the largest one
+        let error = |img: &Image| {
+            let mut sum: f64 = 0.0;
+            let mut max: f64 = 0.0;
+            for h in 0..img.height {
+                for w in 0..img.width {
+                    let expected = if w < img.width / 2 { 0.2 } else { 0.8 };
+                    let error = (img.get_color_pixel(w, h).r() - expected).abs();
+                    sum += error * error;
+                    max = max.max(error);
+                }
+            }
+            ((sum / (img.width * img.height) as f64).sqrt(), max)
+        };
+
+        assert!(error(&denoised).0 < error(&img).0 / 2.0);
+        // The sides of the edge did not bleed into each other
+        assert!(error(&denoised).1 < 0.1);
+
+        // Buffers of another image are refused
+        let smaller = Aovs::new(16, 16);
+        assert!(Denoiser::new().denoise(&img, &smaller, &variance).is_err());
+        assert!(Denoiser::new()
+            .denoise(&img, &aovs, &variance[1..])
+            .is_err());
+    }
+}
@@ -0,0 +1,567 @@
+use crate::{
+    render_tile, scene_hash, settings_hash, Camera, Color, Image, PixelStats, RTError, Ray, Reader,
+    RenderControl, RenderSettings, Tile, World,
+};
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+// The protocol is made of text lines, only the rendered tiles are sent as binary data:
+//
+//   worker       HELLO <scene hash> <settings hash>
+//   coordinator  WELCOME, or REJECT <reason> if the worker does not render the same thing
+//   coordinator  TILE <x> <y> <width> <height>, or END when there is nothing left to do
+//   worker       RESULT <size in bytes> followed by the pixels of the tile
+//
+// and so on until END. A worker that disconnects before sending back its tile, or that stays
+// silent for longer than the timeout, gets it reassigned
+
+// Bytes of the header of the pixels of a tile, then of each pixel
+const HEADER_SIZE: usize = 12;
+const PIXEL_SIZE: usize = 60;
+
+// Workers joining or leaving a distributed render, reported to the observer of its `RenderControl`
+#[derive(Debug, PartialEq, Clone)]
+pub enum WorkerEvent {
+    Connected(SocketAddr),
+    Lost(SocketAddr, String),
+}
+
+struct Work {
+    pending: VecDeque<Tile>,
+    remaining: usize,
+    // Workers connected and not lost yet
+    workers: usize,
+    img: Image,
+}
+
+// Hand out the tiles of `img`, or of its crop window, to the workers connecting to `listener` and put their results
+// together. Returns once every tile has been rendered, or with the tiles done so far when cancelled.
+// `timeout` is how long a worker may stay silent, it must be longer than the render of a tile. It
+// is also how long to wait without any worker while tiles are left, before giving up
+pub fn coordinate(
+    listener: TcpListener,
+    img: Image,
+    scene_hash: u64,
+    settings: &RenderSettings,
+    tile_size: u32,
+    timeout: Duration,
+    control: &mut RenderControl,
+) -> Result<Image, RTError> {
+    let settings_hash = settings_hash(&img, scene_hash, settings);
+    // Pixels a tile can spread its samples to
+    let margin = (settings.filter.radius() + 0.5).ceil() as u32;
+    let area = settings.area(&img);
+    let tiles: Vec<_> = Tile::split(area.width, area.height, tile_size)
+        .into_iter()
+        .map(|tile| Tile::new(area.x + tile.x, area.y + tile.y, tile.width, tile.height))
+        .collect();
+    let work = Arc::new((
+        Mutex::new(Work {
+            remaining: tiles.len(),
+            pending: tiles.into_iter().collect(),
+            workers: 0,
+            img,
+        }),
+        Condvar::new(),
+    ));
+
+    // The threads serving the workers send their events back to be reported from here
+    let (events, received) = mpsc::channel();
+    listener.set_nonblocking(true).map_err(RTError::IO)?;
+    let mut alone_since = Some(Instant::now());
+    loop {
+        for event in received.try_iter() {
+            control.observer.worker(&event);
+        }
+        let (remaining, workers) = {
+            let work = work.0.lock().unwrap();
+            (work.remaining, work.workers)
+        };
+        if remaining == 0 || control.cancellation.is_cancelled() {
+            break;
+        }
+        match alone_since {
+            Some(since) if workers == 0 && since.elapsed() > timeout => {
+                return Err(RTError::Protocol(format!(
+                    "no worker for {} s with {} tiles left",
+                    timeout.as_secs_f64(),
+                    remaining
+                )));
+            }
+            Some(_) if workers > 0 => alone_since = None,
+            None if workers == 0 => alone_since = Some(Instant::now()),
+            _ => {}
+        }
+
+        match listener.accept() {
+            Ok((stream, address)) => {
+                control.observer.worker(&WorkerEvent::Connected(address));
+                stream.set_nonblocking(false).map_err(RTError::IO)?;
+                stream
+                    .set_read_timeout(Some(timeout))
+                    .map_err(RTError::IO)?;
+                stream
+                    .set_write_timeout(Some(timeout))
+                    .map_err(RTError::IO)?;
+                work.0.lock().unwrap().workers += 1;
+                let work = Arc::clone(&work);
+                let events = events.clone();
+                thread::spawn(move || {
+                    let served = serve_worker(stream, &work, scene_hash, settings_hash, margin);
+                    work.0.lock().unwrap().workers -= 1;
+                    if let Err(e) = served {
+                        let _ = events.send(WorkerEvent::Lost(address, e.to_string()));
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(20));
+            }
+            Err(e) => return Err(RTError::IO(e)),
+        }
+    }
+
+    let mut work = work.0.lock().unwrap();
+    let (width, height) = (work.img.width, work.img.height);
+    Ok(std::mem::replace(&mut work.img, Image::new(width, height)))
+}
+
+fn serve_worker(
+    stream: TcpStream,
+    work: &(Mutex<Work>, Condvar),
+    scene_hash: u64,
+    settings_hash: u64,
+    margin: u32,
+) -> Result<(), RTError> {
+    let mut writer = stream.try_clone().map_err(RTError::IO)?;
+    let mut reader = BufReader::new(stream);
+
+    let hello = read_line(&mut reader)?;
+    let expected = format!("HELLO {:016x} {:016x}", scene_hash, settings_hash);
+    if hello != expected {
+        send_line(&mut writer, "REJECT not the same scene or settings")?;
+        return Err(RTError::Protocol(format!("unexpected greeting {}", hello)));
+    }
+    send_line(&mut writer, "WELCOME")?;
+
+    let (lock, condvar) = work;
+    loop {
+        // Wait for a tile, some may come back from lost workers until everything is done
+        let tile = {
+            let mut work = lock.lock().unwrap();
+            loop {
+                if work.remaining == 0 {
+                    break None;
+                }
+                if let Some(tile) = work.pending.pop_front() {
+                    break Some(tile);
+                }
+                work = condvar
+                    .wait_timeout(work, Duration::from_millis(100))
+                    .unwrap()
+                    .0;
+            }
+        };
+        let tile = match tile {
+            Some(tile) => tile,
+            None => return send_line(&mut writer, "END"),
+        };
+        let (width, height) = {
+            let work = lock.lock().unwrap();
+            (work.img.width, work.img.height)
+        };
+        let (x, y) = (tile.x.saturating_sub(margin), tile.y.saturating_sub(margin));
+        let reach = Tile::new(
+            x,
+            y,
+            (tile.x + tile.width + margin).min(width) - x,
+            (tile.y + tile.height + margin).min(height) - y,
+        );
+
+        match render_remotely(&mut reader, &mut writer, &tile, &reach, width, height) {
+            Ok(tile_img) => {
+                let mut work = lock.lock().unwrap();
+                work.img.merge(&tile_img);
+                work.remaining -= 1;
+                condvar.notify_all();
+            }
+            Err(e) => {
+                lock.lock().unwrap().pending.push_back(tile);
+                condvar.notify_all();
+                return Err(e);
+            }
+        }
+    }
+}
+
+// The answer is checked before being merged: the pixels must be the ones of an image of
+// `width` x `height` pixels, within `reach` of the tile
+fn render_remotely(
+    reader: &mut BufReader<TcpStream>,
+    writer: &mut TcpStream,
+    tile: &Tile,
+    reach: &Tile,
+    width: u32,
+    height: u32,
+) -> Result<Image, RTError> {
+    send_line(
+        writer,
+        &format!("TILE {} {} {} {}", tile.x, tile.y, tile.width, tile.height),
+    )?;
+
+    let line = read_line(reader)?;
+    let size: usize = match line.strip_prefix("RESULT ").map(str::parse) {
+        Some(Ok(size)) => size,
+        _ => return Err(RTError::Protocol(format!("unexpected answer {}", line))),
+    };
+    let max_size = HEADER_SIZE + (reach.width * reach.height) as usize * PIXEL_SIZE;
+    if size > max_size {
+        return Err(RTError::Protocol(format!(
+            "{} bytes for a tile of at most {}",
+            size, max_size
+        )));
+    }
+    let mut bytes = vec![0; size];
+    reader.read_exact(&mut bytes).map_err(RTError::IO)?;
+
+    let img = decode_pixels(&bytes, width, height)?;
+    if let Some((w, h)) = img.pixels.keys().find(|(w, h)| !reach.contains(*w, *h)) {
+        return Err(RTError::Protocol(format!(
+            "pixel ({}, {}) out of the tile",
+            w, h
+        )));
+    }
+    Ok(img)
+}
+
+// Connect to the coordinator at `address` and render the tiles it asks for until it says it is over.
+// The worker must have the same scene, camera, image size and settings as the coordinator
+pub fn work<F>(
+    address: &str,
+    img: &Image,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Result<(), RTError>
+where
+    F: Fn(&Ray) -> Color,
+{
+    let stream = TcpStream::connect(address).map_err(RTError::IO)?;
+    let mut writer = stream.try_clone().map_err(RTError::IO)?;
+    let mut reader = BufReader::new(stream);
+
+    let scene_hash = scene_hash(world, camera);
+    send_line(
+        &mut writer,
+        &format!(
+            "HELLO {:016x} {:016x}",
+            scene_hash,
+            settings_hash(img, scene_hash, settings)
+        ),
+    )?;
+    let answer = read_line(&mut reader)?;
+    if answer != "WELCOME" {
+        return Err(RTError::Protocol(format!("rejected: {}", answer)));
+    }
+
+    loop {
+        let line = read_line(&mut reader)?;
+        if line == "END" {
+            return Ok(());
+        }
+
+        let values: Vec<u32> = match line.strip_prefix("TILE ") {
+            Some(values) => values
+                .split(' ')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| RTError::Protocol(format!("bad tile {}", line)))?,
+            None => return Err(RTError::Protocol(format!("unexpected order {}", line))),
+        };
+        let tile = match values[..] {
+            [x, y, width, height] => Tile::new(x, y, width, height),
+            _ => return Err(RTError::Protocol(format!("bad tile {}", line))),
+        };
+
+        let tile_img = render_tile(img.width, img.height, &tile, world, camera, settings);
+        let bytes = encode_pixels(&tile_img);
+        send_line(&mut writer, &format!("RESULT {}", bytes.len()))?;
+        writer.write_all(&bytes).map_err(RTError::IO)?;
+    }
+}
+
+fn send_line(writer: &mut TcpStream, line: &str) -> Result<(), RTError> {
+    writer
+        .write_all(format!("{}\n", line).as_bytes())
+        .map_err(RTError::IO)
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String, RTError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(RTError::IO)? == 0 {
+        return Err(RTError::Protocol("connection closed".to_string()));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+// Only the pixels present in the image are sent, with their coordinates
+fn encode_pixels(img: &Image) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&img.width.to_le_bytes());
+    bytes.extend_from_slice(&img.height.to_le_bytes());
+    bytes.extend_from_slice(&(img.pixels.len() as u32).to_le_bytes());
+
+    for (&(w, h), sum) in img.pixels.iter() {
+        let weight = img.weights.get(&(w, h)).copied().unwrap_or(0.0);
+        let stats = img.get_pixel_stats(w, h);
+
+        bytes.extend_from_slice(&w.to_le_bytes());
+        bytes.extend_from_slice(&h.to_le_bytes());
+        for value in [sum.r(), sum.g(), sum.b(), weight].iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&stats.count.to_le_bytes());
+        bytes.extend_from_slice(&stats.mean.to_le_bytes());
+        bytes.extend_from_slice(&stats.m2.to_le_bytes());
+    }
+
+    bytes
+}
+
+// The pixels of a tile of an image of `width` x `height` pixels
+fn decode_pixels(bytes: &[u8], width: u32, height: u32) -> Result<Image, RTError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let read = |reader: &mut Reader| -> Result<Image, RTError> {
+        let size = (reader.u32()?, reader.u32()?);
+        if size != (width, height) {
+            return Err(RTError::Protocol(format!(
+                "tile of a {}x{} image instead of {}x{}",
+                size.0, size.1, width, height
+            )));
+        }
+        let mut img = Image::new(width, height);
+        for _ in 0..reader.u32()? {
+            let key = (reader.u32()?, reader.u32()?);
+            let sum = Color::new(reader.f64()?, reader.f64()?, reader.f64()?);
+            let weight = reader.f64()?;
+            let stats = PixelStats {
+                count: reader.u32()?,
+                mean: reader.f64()?,
+                m2: reader.f64()?,
+            };
+
+            img.pixels.insert(key, sum);
+            img.weights.insert(key, weight);
+            if stats.count != 0 {
+                img.stats.insert(key, stats);
+            }
+        }
+        Ok(img)
+    };
+
+    read(&mut reader).map_err(|e| RTError::Protocol(format!("bad tile data: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Vec3, render, CropWindow};
+
+    fn gray_world() -> World<impl Fn(&Ray) -> Color> {
+        World::new(|ray: &Ray| Color::new(0.5, 0.5, 0.5 + 0.1 * ray.direction.y))
+    }
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    fn start_coordinator(
+        img: Image,
+        settings: &RenderSettings,
+    ) -> (String, thread::JoinHandle<Result<Image, RTError>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let scene_hash = scene_hash(&gray_world(), &camera());
+        let settings = *settings;
+
+        let handle = thread::spawn(move || {
+            coordinate(
+                listener,
+                img,
+                scene_hash,
+                &settings,
+                3,
+                Duration::from_millis(300),
+                &mut RenderControl::silent(),
+            )
+        });
+        (address, handle)
+    }
+
+    // Connects and says hello like a worker, leaving the rest to the test
+    fn fake_worker(
+        address: &str,
+        img: &Image,
+        settings: &RenderSettings,
+    ) -> (BufReader<TcpStream>, TcpStream) {
+        let stream = TcpStream::connect(address).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let scene_hash = scene_hash(&gray_world(), &camera());
+        send_line(
+            &mut writer,
+            &format!(
+                "HELLO {:016x} {:016x}",
+                scene_hash,
+                settings_hash(img, scene_hash, settings)
+            ),
+        )
+        .unwrap();
+        assert_eq!(read_line(&mut reader).unwrap(), "WELCOME");
+        (reader, writer)
+    }
+
+    #[test]
+    fn workers_render_every_tile() {
+        let settings = RenderSettings::new(4, 10);
+        let (address, coordinator) = start_coordinator(Image::new(10, 7), &settings);
+
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let address = address.clone();
+                thread::spawn(move || {
+                    work(
+                        &address,
+                        &Image::new(10, 7),
+                        &gray_world(),
+                        &camera(),
+                        &settings,
+                    )
+                })
+            })
+            .collect();
+        // Workers coming once everything is done find nobody, one of them at least did the job
+        let done = workers
+            .into_iter()
+            .filter_map(|worker| worker.join().unwrap().ok())
+            .count();
+        assert!(done >= 1);
+
+        let img = coordinator.join().unwrap().unwrap();
+        let local = render(Image::new(10, 7), &gray_world(), &camera(), &settings);
+        assert_eq!(img.pixels.len(), 70);
+        for h in 0..7 {
+            for w in 0..10 {
+                assert_eq!(img.get_pixel_stats(w, h).count, 4);
+                let color = img.get_color_pixel(w, h).vec - local.get_color_pixel(w, h).vec;
+                assert!(color.length() < 0.03);
+            }
+        }
+    }
+
+    #[test]
+    fn workers_only_render_the_crop_window() {
+        let mut settings = RenderSettings::new(2, 10);
+        let window = Tile::new(4, 2, 5, 4);
+        settings.crop = Some(CropWindow::Pixels(window));
+        let (address, coordinator) = start_coordinator(Image::new(10, 7), &settings);
+
+        work(
+            &address,
+            &Image::new(10, 7),
+            &gray_world(),
+            &camera(),
+            &settings,
+        )
+        .unwrap();
+        let img = coordinator.join().unwrap().unwrap();
+        for h in 0..7 {
+            for w in 0..10 {
+                let expected = if window.contains(w, h) { 2 } else { 0 };
+                assert_eq!(img.get_pixel_stats(w, h).count, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn coordinator_gives_up_without_workers() {
+        let settings = RenderSettings::new(2, 10);
+        let (_, coordinator) = start_coordinator(Image::new(6, 6), &settings);
+        assert!(matches!(
+            coordinator.join().unwrap(),
+            Err(RTError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn lost_worker_tiles_are_reassigned() {
+        let settings = RenderSettings::new(2, 10);
+        let (address, coordinator) = start_coordinator(Image::new(6, 6), &settings);
+
+        // This one takes a tile and disconnects without answering
+        let img = Image::new(6, 6);
+        let (mut reader, writer) = fake_worker(&address, &img, &settings);
+        assert!(read_line(&mut reader).unwrap().starts_with("TILE "));
+        drop(reader);
+        drop(writer);
+
+        // This one takes a tile and hangs, until the coordinator gives up on it
+        let (mut hanging, _writer) = fake_worker(&address, &img, &settings);
+        assert!(read_line(&mut hanging).unwrap().starts_with("TILE "));
+
+        // This one announces far more data than a tile can hold
+        let (mut reader, mut writer) = fake_worker(&address, &img, &settings);
+        assert!(read_line(&mut reader).unwrap().starts_with("TILE "));
+        send_line(&mut writer, "RESULT 1000000000000").unwrap();
+        assert!(read_line(&mut reader).is_err());
+
+        work(&address, &img, &gray_world(), &camera(), &settings).unwrap();
+
+        let img = coordinator.join().unwrap().unwrap();
+        for h in 0..6 {
+            for w in 0..6 {
+                assert_eq!(img.get_pixel_stats(w, h).count, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn worker_with_other_settings_is_rejected() {
+        let settings = RenderSettings::new(2, 10);
+        let (address, coordinator) = start_coordinator(Image::new(4, 4), &settings);
+
+        let other_settings = RenderSettings::new(3, 10);
+        let result = work(
+            &address,
+            &Image::new(4, 4),
+            &gray_world(),
+            &camera(),
+            &other_settings,
+        );
+        assert!(matches!(result, Err(RTError::Protocol(_))));
+
+        work(
+            &address,
+            &Image::new(4, 4),
+            &gray_world(),
+            &camera(),
+            &settings,
+        )
+        .unwrap();
+        coordinator.join().unwrap().unwrap();
+    }
+}
@@ -1,4 +1,6 @@
+use exr::error::Error as ExrError;
 use image::ImageError;
+use png::EncodingError as PngError;
 use std::{
     error::Error,
     fmt::{Display, Formatter},
@@ -9,8 +11,15 @@ use std::{
 pub enum RTError {
     IO(IOError),
     ImageRS(ImageError),
+    Exr(ExrError),
+    Png(PngError),
     EmptyImg,
     InconsistencySizePixels { h: u32, w: u32, nb_pixels: usize },
+    InvalidArgument(String),
+    InvalidCheckpoint(String),
+    CheckpointMismatch { expected: u64, found: u64 },
+    MergeMismatch(String),
+    Protocol(String),
 }
 
 impl Display for RTError {
@@ -20,11 +29,24 @@ impl Display for RTError {
             // This is a wrapper, so defer to the underlying types' implementation of `fmt`.
             RTError::IO(ref e) => e.fmt(f),
             RTError::ImageRS(ref e) => e.fmt(f),
+            RTError::Exr(ref e) => e.fmt(f),
+            RTError::Png(ref e) => e.fmt(f),
             RTError::InconsistencySizePixels { h, w, nb_pixels } => write!(
                 f,
                 "The size {}*{} do not equals the nb of pixels {}",
                 h, w, nb_pixels
             ),
+            RTError::InvalidArgument(ref arg) => write!(f, "Invalid argument: {}", arg),
+            RTError::InvalidCheckpoint(ref reason) => write!(f, "Invalid checkpoint: {}", reason),
+            RTError::CheckpointMismatch { expected, found } => write!(
+                f,
+                "The checkpoint was made with another scene or other settings (hash {:016x} instead of {:016x})",
+                found, expected
+            ),
+            RTError::MergeMismatch(ref reason) => {
+                write!(f, "Cannot merge these renders: {}", reason)
+            }
+            RTError::Protocol(ref reason) => write!(f, "Distributed rendering failed: {}", reason),
         }
     }
 }
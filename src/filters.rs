@@ -0,0 +1,110 @@
+use crate::math::PI;
+
+// Reconstruction filters, they weight each sample according to its distance (in pixels)
+// from the center of the pixels around it
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Filter {
+    // Only the pixel the sample falls in, with the same weight: a plain average
+    #[default]
+    Box,
+    Tent {
+        radius: f64,
+    },
+    Gaussian {
+        radius: f64,
+        alpha: f64,
+    },
+    // Mitchell-Netravali cubic, B = C = 1/3 is the usual choice
+    Mitchell {
+        b: f64,
+        c: f64,
+    },
+    // Windowed sinc with `lobes` lobes on each side
+    Lanczos {
+        lobes: u32,
+    },
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box => 0.5,
+            Filter::Tent { radius } => radius,
+            Filter::Gaussian { radius, .. } => radius,
+            Filter::Mitchell { .. } => 2.0,
+            Filter::Lanczos { lobes } => lobes as f64,
+        }
+    }
+
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        let radius = self.radius();
+        if dx.abs() >= radius || dy.abs() >= radius {
+            return 0.0;
+        }
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match *self {
+            Filter::Box => 1.0,
+            Filter::Tent { radius } => f64::max(0.0, 1.0 - x / radius),
+            Filter::Gaussian { radius, alpha } => f64::max(
+                0.0,
+                (-alpha * x * x).exp() - (-alpha * radius * radius).exp(),
+            ),
+            Filter::Mitchell { b, c } => {
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos { lobes } => sinc(x) * sinc(x / lobes as f64),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_peak_at_center() {
+        let filters = [
+            Filter::Box,
+            Filter::Tent { radius: 1.0 },
+            Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            Filter::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            Filter::Lanczos { lobes: 3 },
+        ];
+
+        for filter in filters.iter() {
+            let center = filter.weight(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.weight(0.3, 0.2) <= center);
+            assert_eq!(filter.weight(filter.radius(), 0.0), 0.0);
+        }
+    }
+}
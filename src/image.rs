@@ -1,7 +1,6 @@
-use crate::{clamp, math::Vec3, Camera, RTError, Ray, World};
-use image::ImageBuffer;
-use rand::Rng;
-use std::{collections::HashMap, ops, time::Instant};
+use crate::{clamp, math::Vec3, RTError};
+use image::{ImageBuffer, RgbImage};
+use std::{collections::HashMap, ops};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Color {
@@ -23,6 +22,40 @@ impl Color {
         Color::new_with_vec(Vec3::new_random(0.0, 1.0))
     }
 
+    // Color of a black body at the temperature `kelvin`, in linear sRGB with a luminance of 1
+    pub fn new_blackbody(kelvin: f64) -> Self {
+        // Multi-lobe gaussian fit of the CIE 1931 matching functions (Wyman, Sloan & Shirley 2013)
+        fn g(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+            let sigma = if x < mu { sigma1 } else { sigma2 };
+            (-0.5 * ((x - mu) / sigma).powi(2)).exp()
+        }
+
+        // Planck's law, constant factors are dropped since the result is normalized anyway
+        const HC_OVER_K: f64 = 1.438_777e7; // in nm.K
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for lambda in (380..=780).step_by(5) {
+            let l = lambda as f64;
+            let radiance = 1.0 / (l.powi(5) * ((HC_OVER_K / (l * kelvin)).exp() - 1.0));
+            x += radiance
+                * (1.056 * g(l, 599.8, 37.9, 31.0) + 0.362 * g(l, 442.0, 16.0, 26.7)
+                    - 0.065 * g(l, 501.1, 20.4, 26.2));
+            y += radiance * (0.821 * g(l, 568.8, 46.9, 40.5) + 0.286 * g(l, 530.9, 16.3, 31.1));
+            z += radiance * (1.217 * g(l, 437.0, 11.8, 36.0) + 0.681 * g(l, 459.0, 26.0, 13.8));
+        }
+        let (x, z) = (x / y, z / y);
+
+        // XYZ to linear sRGB
+        Color::new(
+            f64::max(3.2406 * x - 1.5372 - 0.4986 * z, 0.0),
+            f64::max(-0.9689 * x + 1.8758 + 0.0415 * z, 0.0),
+            f64::max(0.0557 * x - 0.2040 + 1.0570 * z, 0.0),
+        )
+    }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
     pub fn r(&self) -> f64 {
         self.vec.x
     }
@@ -38,7 +71,7 @@ impl ops::Mul<Color> for Color {
     type Output = Color;
 
     fn mul(self, other: Color) -> Color {
-        Color::new_with_vec(&self.vec * &other.vec)
+        Color::new_with_vec(self.vec * other.vec)
     }
 }
 
@@ -46,13 +79,64 @@ impl ops::Add<Color> for Color {
     type Output = Color;
 
     fn add(self, other: Color) -> Color {
-        Color::new_with_vec(&self.vec + &other.vec)
+        Color::new_with_vec(self.vec + other.vec)
+    }
+}
+
+// Running mean and variance (Welford's algorithm) of the luminance of the samples of a pixel
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PixelStats {
+    pub count: u32,
+    pub mean: f64,
+    pub m2: f64,
+}
+
+impl PixelStats {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // Combine the stats of two independent sets of samples (Chan et al.)
+    pub fn merge(&mut self, other: &PixelStats) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta.powi(2) * self.count as f64 * other.count as f64 / count as f64;
+        self.count = count;
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    // Standard error of the mean relative to the mean itself
+    pub fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        (self.variance() / self.count as f64).sqrt() / f64::max(self.mean, 1e-4)
     }
 }
 
-#[derive(Debug, PartialEq)]
+const MIN_PIXEL_WEIGHT: f64 = 1e-3;
+
+// `pixels` holds the weighted sum of the samples of each pixel and `weights` the sum of their weights,
+// `stats` tracks the samples taken for each pixel before filtering
+#[derive(Debug, PartialEq, Clone)]
 pub struct Image {
     pub pixels: HashMap<(u32, u32), Color>,
+    pub weights: HashMap<(u32, u32), f64>,
+    pub stats: HashMap<(u32, u32), PixelStats>,
     pub width: u32,
     pub height: u32,
 }
@@ -60,74 +144,116 @@ pub struct Image {
 impl Image {
     pub fn new(width: u32, height: u32) -> Self {
         let pixels = HashMap::with_capacity((height * width) as usize);
+        let weights = HashMap::with_capacity((height * width) as usize);
         Image {
             pixels,
+            weights,
+            stats: HashMap::new(),
             width,
             height,
         }
     }
 
+    pub fn add_sample_stats(&mut self, width: u32, height: u32, color: &Color) {
+        self.stats
+            .entry((width, height))
+            .or_default()
+            .add(color.luminance());
+    }
+
+    pub fn get_pixel_stats(&self, width: u32, height: u32) -> PixelStats {
+        self.stats
+            .get(&(width, height))
+            .copied()
+            .unwrap_or_default()
+    }
+
     pub fn add_pixel(&mut self, width: u32, height: u32, color: Color) {
         self.pixels.insert((width, height), color);
+        self.weights.insert((width, height), 1.0);
     }
 
-    pub fn get_color_pixel(&self, width: u32, height: u32) -> Color {
-        let mut color = self.pixels.get(&(width, height));
-        let default = Color::new(0.0, 0.0, 0.0);
-        **color.get_or_insert(&default)
+    // Accumulate a sample with its filter weight
+    pub fn splat(&mut self, width: u32, height: u32, color: Color, weight: f64) {
+        let pixel = self
+            .pixels
+            .entry((width, height))
+            .or_insert(Color::new(0.0, 0.0, 0.0));
+        pixel.vec += weight * color.vec;
+        *self.weights.entry((width, height)).or_insert(0.0) += weight;
     }
-}
 
-pub fn create_img<F>(
-    mut img: Image,
-    world: World<F>,
-    samples_per_pixel: u32,
-    camera: Camera,
-    depth: u32,
-) -> Image
-where
-    F: Fn(&Ray) -> Color,
-{
-    let mut rng = rand::thread_rng();
-
-    let total_rays_to_trace: u64 = img.height as u64 * img.width as u64 * samples_per_pixel as u64;
-    let mut ray_traced: u64 = 0;
-
-    let mut timer = Instant::now();
-
-    for h in (0..img.height).rev() {
-        for w in 0..img.width {
-            // let progression = ((i+1)*(j+1)) as f64 / (img_height*img_width) as f64;
-            // eprintln!("Progression: {}%", progression*100.0);
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..samples_per_pixel {
-                let u = (w as f64 + rng.gen_range(0.0..1.0)) / (img.width - 1) as f64;
-                let v = (h as f64 + rng.gen_range(0.0..1.0)) / (img.height - 1) as f64;
-                let ray: Ray = camera.get_ray(u, v);
-
-                let ray_color = ray.ray_color(&world, depth);
-                pixel_color.vec = pixel_color.vec + ray_color.vec;
-
-                ray_traced = ray_traced + 1;
-                if timer.elapsed().as_secs_f64() >= 1.0 {
-                    let percent = ray_traced as f64 / total_rays_to_trace as f64 * 100.0;
-                    println!(
-                        "{:.2}% done, {} over {}",
-                        percent, ray_traced, total_rays_to_trace
-                    );
-                    timer = Instant::now();
-                }
-            }
-            pixel_color.vec = pixel_color.vec / samples_per_pixel as f64;
-            img.add_pixel(w, h, pixel_color);
+    // Add the samples of another render of the same size
+    pub fn merge(&mut self, other: &Image) {
+        for (key, color) in other.pixels.iter() {
+            let pixel = self.pixels.entry(*key).or_insert(Color::new(0.0, 0.0, 0.0));
+            pixel.vec += color.vec;
+        }
+        for (key, weight) in other.weights.iter() {
+            *self.weights.entry(*key).or_insert(0.0) += weight;
+        }
+        for (key, stats) in other.stats.iter() {
+            self.stats.entry(*key).or_default().merge(stats);
+        }
+    }
+
+    // Copy `other` into this image, its bottom left pixel going to (x, y)
+    pub fn paste(&mut self, other: &Image, x: u32, y: u32) {
+        for ((w, h), color) in other.pixels.iter() {
+            self.pixels.insert((x + w, y + h), *color);
+        }
+        for ((w, h), weight) in other.weights.iter() {
+            self.weights.insert((x + w, y + h), *weight);
+        }
+        for ((w, h), stats) in other.stats.iter() {
+            self.stats.insert((x + w, y + h), *stats);
         }
     }
 
-    img
+    // The `width` x `height` pixels whose bottom left one is (x, y), as an image of their own
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Image {
+        let inside = |(w, h): &(u32, u32)| *w >= x && *w < x + width && *h >= y && *h < y + height;
+        let mut cropped = Image::new(width, height);
+        for (key, color) in self.pixels.iter().filter(|(key, _)| inside(key)) {
+            cropped.pixels.insert((key.0 - x, key.1 - y), *color);
+        }
+        for (key, weight) in self.weights.iter().filter(|(key, _)| inside(key)) {
+            cropped.weights.insert((key.0 - x, key.1 - y), *weight);
+        }
+        for (key, stats) in self.stats.iter().filter(|(key, _)| inside(key)) {
+            cropped.stats.insert((key.0 - x, key.1 - y), *stats);
+        }
+        cropped
+    }
+
+    // Filters with negative lobes (Mitchell, Lanczos) can leave a pixel on the edge of a render with
+    // next to no weight, or a negative one: dividing by it would blow the color up, so it is black
+    pub fn get_color_pixel(&self, width: u32, height: u32) -> Color {
+        match (
+            self.pixels.get(&(width, height)),
+            self.weights.get(&(width, height)),
+        ) {
+            (Some(color), Some(weight)) if *weight > MIN_PIXEL_WEIGHT => {
+                Color::new_with_vec(color.vec / *weight)
+            }
+            (Some(color), None) => *color,
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
 }
 
 pub fn write_img_to_file(path: &str, img: &Image) -> Result<(), RTError> {
-    if img.height * img.width != img.pixels.len() as u32 {
+    to_rgb8(img)?.save(path).map_err(RTError::ImageRS)
+}
+
+// 8 bits per channel with a gamma of 2, the top row first. The pixels not rendered yet, in a
+// snapshot or a cancelled render, are black
+pub(crate) fn to_rgb8(img: &Image) -> Result<RgbImage, RTError> {
+    if img
+        .pixels
+        .keys()
+        .any(|(w, h)| *w >= img.width || *h >= img.height)
+    {
         return Err(RTError::InconsistencySizePixels {
             h: img.height,
             w: img.width,
@@ -135,16 +261,54 @@ pub fn write_img_to_file(path: &str, img: &Image) -> Result<(), RTError> {
         });
     };
 
-    let img_to_write = ImageBuffer::from_fn(img.width, img.height, |w, h| {
-        let color = img.get_color_pixel(w, img.height - h);
+    Ok(ImageBuffer::from_fn(img.width, img.height, |w, h| {
+        let color = img.get_color_pixel(w, img.height - 1 - h);
         let r = (256.0 * clamp(color.r().sqrt(), 0.0, 0.999)) as u8;
         let g = (256.0 * clamp(color.g().sqrt(), 0.0, 0.999)) as u8;
         let b = (256.0 * clamp(color.b().sqrt(), 0.0, 0.999)) as u8;
 
+        image::Rgb([r, g, b])
+    }))
+}
+
+// Image written by `write_img_to_file`, back as one sample per pixel. The samples and the
+// precision lost in the file are not recovered
+pub fn read_img_from_file(path: &str) -> Result<Image, RTError> {
+    let file = image::open(path).map_err(RTError::ImageRS)?.to_rgb8();
+    let mut img = Image::new(file.width(), file.height());
+    for (w, h, pixel) in file.enumerate_pixels() {
+        // Middle of the range of values written as this byte
+        let channel = |value: u8| ((value as f64 + 0.5) / 256.0).powi(2);
+        let color = Color::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2]));
+        img.add_pixel(w, img.height - 1 - h, color);
+    }
+    Ok(img)
+}
+
+// Number of samples taken by each pixel, from blue (the fewest) to red (the most)
+pub fn write_sample_heatmap_to_file(path: &str, img: &Image) -> Result<(), RTError> {
+    if img.stats.is_empty() {
+        return Err(RTError::EmptyImg);
+    }
+
+    let min = img.stats.values().map(|s| s.count).min().unwrap_or(0) as f64;
+    let max = img.stats.values().map(|s| s.count).max().unwrap_or(0) as f64;
+
+    let img_to_write = ImageBuffer::from_fn(img.width, img.height, |w, h| {
+        let count = img.get_pixel_stats(w, img.height - 1 - h).count as f64;
+        let t = if max > min {
+            (count - min) / (max - min)
+        } else {
+            0.0
+        };
+        let r = (255.0 * clamp(2.0 * t - 0.5, 0.0, 1.0)) as u8;
+        let g = (255.0 * clamp(1.5 - (4.0 * t - 2.0).abs(), 0.0, 1.0)) as u8;
+        let b = (255.0 * clamp(1.5 - 2.0 * t, 0.0, 1.0)) as u8;
+
         image::Rgb([r, g, b])
     });
 
-    img_to_write.save(path).map_err(|e| RTError::ImageRS(e))
+    img_to_write.save(path).map_err(RTError::ImageRS)
 }
 
 // #[cfg(test)]
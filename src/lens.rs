@@ -0,0 +1,206 @@
+use crate::{math::TAU, RTError};
+use rand::Rng;
+
+// Shape of the opening of the lens, which is the shape of the out of focus highlights (bokeh)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    // Regular polygon made by `blades` straight blades, turned by `rotation` radians
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    Mask(ApertureMask),
+}
+
+// An aperture drawn in an image: the brighter a pixel, the more light goes through it
+#[derive(Debug, PartialEq, Clone)]
+pub struct ApertureMask {
+    pub width: u32,
+    pub height: u32,
+    // Running sum of the weights of the pixels, row after row from the top
+    cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn new(width: u32, height: u32, weights: &[f64]) -> Result<Self, RTError> {
+        if weights.len() != (width * height) as usize {
+            return Err(RTError::InconsistencySizePixels {
+                h: height,
+                w: width,
+                nb_pixels: weights.len(),
+            });
+        }
+        let cdf: Vec<f64> = weights
+            .iter()
+            .scan(0.0, |sum, weight| {
+                *sum += weight.max(0.0);
+                Some(*sum)
+            })
+            .collect();
+        if cdf.last().is_none_or(|total| *total <= 0.0) {
+            return Err(RTError::EmptyImg);
+        }
+
+        Ok(ApertureMask { width, height, cdf })
+    }
+
+    pub fn open(path: &str) -> Result<Self, RTError> {
+        let img = image::open(path).map_err(RTError::ImageRS)?.to_luma8();
+        let (width, height) = img.dimensions();
+        let weights: Vec<f64> = img.pixels().map(|p| p[0] as f64 / 255.0).collect();
+        ApertureMask::new(width, height, &weights)
+    }
+
+    // Point of [-1, 1] x [-1, 1], the image covering that square
+    fn sample(&self) -> (f64, f64) {
+        let mut rng = rand::thread_rng();
+        let target = rng.gen_range(0.0..1.0) * self.cdf[self.cdf.len() - 1];
+        let i = self
+            .cdf
+            .partition_point(|sum| *sum <= target)
+            .min(self.cdf.len() - 1) as u32;
+        let (column, row) = (i % self.width, i / self.width);
+
+        let x = (column as f64 + rng.gen_range(0.0..1.0)) / self.width as f64;
+        let y = (row as f64 + rng.gen_range(0.0..1.0)) / self.height as f64;
+        (2.0 * x - 1.0, 1.0 - 2.0 * y)
+    }
+}
+
+// What the lens does besides focusing. `cat_eye` is the optical vignetting: the barrel of the lens
+// hides more and more of the aperture toward the edges of the image, giving the bokeh a cat's eye
+// shape there and darkening them, 0 turns it off. The radial distortion moves a point of the image
+// at the distance r from the center (1 at the corners) to r (1 + k1 r² + k2 r⁴): positive values
+// make barrel distortion, negative ones pincushion. With lateral chromatic aberration the red channel is
+// magnified by 1 + `chromatic_aberration` and the blue one by 1 - `chromatic_aberration`.
+// A tilt-shift lens turns the plane in focus around the horizontal axis of the image by `tilt.0`
+// radians, the top going further away when positive, and around the vertical one by `tilt.1`, the
+// right going further away when positive
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Lens {
+    pub aperture: Aperture,
+    pub cat_eye: f64,
+    pub distortion: (f64, f64),
+    pub chromatic_aberration: f64,
+    pub tilt: (f64, f64),
+}
+
+impl Lens {
+    // Point of the aperture, within the unit disk for the circle and the polygons, seen from the
+    // point (x, y) of the image relative to its center. None when the barrel hides that point: the
+    // sample brings no light, so the image gets as much as the part of the aperture left open
+    pub fn sample_aperture(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let point = self.sample_shape();
+        let barrel = (self.cat_eye * x, self.cat_eye * y);
+        if self.cat_eye > 0.0 && (point.0 - barrel.0).powi(2) + (point.1 - barrel.1).powi(2) > 1.0 {
+            return None;
+        }
+        Some(point)
+    }
+
+    fn sample_shape(&self) -> (f64, f64) {
+        match &self.aperture {
+            Aperture::Circle => {
+                let mut rng = rand::thread_rng();
+                loop {
+                    let (x, y) = (rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+                    if x * x + y * y < 1.0 {
+                        break (x, y);
+                    }
+                }
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // Uniform point in one of the triangles between the center and two corners
+                let mut rng = rand::thread_rng();
+                let blades = (*blades).max(3);
+                let k = rng.gen_range(0..blades) as f64;
+                let corner = |k: f64| {
+                    let angle = rotation + k * TAU / blades as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(k), corner(k + 1.0));
+                let (mut r1, mut r2) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+                if r1 + r2 > 1.0 {
+                    r1 = 1.0 - r1;
+                    r2 = 1.0 - r2;
+                }
+                (r1 * a.0 + r2 * b.0, r1 * a.1 + r2 * b.1)
+            }
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+
+    // Where the point (x, y) of the image, relative to its center, really looks at through the lens
+    // for a channel magnified by `magnification`
+    pub fn distort(&self, x: f64, y: f64, magnification: f64) -> (f64, f64) {
+        let (k1, k2) = self.distortion;
+        let r2 = x * x + y * y;
+        let scale = magnification * (1.0 + k1 * r2 + k2 * r2 * r2);
+        (x * scale, y * scale)
+    }
+
+    pub fn channel_magnification(&self, channel: usize) -> f64 {
+        1.0 + self.chromatic_aberration * (1.0 - channel as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::PI;
+
+    #[test]
+    fn apertures_sample_their_shape() {
+        let hexagon = Lens {
+            aperture: Aperture::Polygon {
+                blades: 6,
+                rotation: 0.0,
+            },
+            ..Lens::default()
+        };
+        // Inside a hexagon with a corner on the x axis, the apothem being sqrt(3) / 2
+        for _ in 0..1000 {
+            let (x, y) = hexagon.sample_aperture(0.0, 0.0).unwrap();
+            for k in 0..6 {
+                let angle = TAU / 12.0 + k as f64 * TAU / 6.0;
+                assert!(x * angle.cos() + y * angle.sin() <= 3f64.sqrt() / 2.0 + 1e-9);
+            }
+        }
+
+        // Only the top right pixel lets light through
+        let mask = ApertureMask::new(2, 2, &[0.0, 1.0, 0.0, 0.0]).unwrap();
+        let lens = Lens {
+            aperture: Aperture::Mask(mask),
+            ..Lens::default()
+        };
+        for _ in 0..100 {
+            let (x, y) = lens.sample_aperture(0.0, 0.0).unwrap();
+            assert!(x >= 0.0 && y >= 0.0);
+        }
+        assert!(ApertureMask::new(2, 2, &[0.0; 4]).is_err());
+
+        // Toward the right of the image the aperture loses its left part, and the light going
+        // through it: two unit disks 1 apart overlap on (2 pi / 3 - sqrt(3) / 2) / pi of each
+        let vignetted = Lens {
+            cat_eye: 1.0,
+            ..Lens::default()
+        };
+        let samples: Vec<_> = (0..10000)
+            .filter_map(|_| vignetted.sample_aperture(1.0, 0.0))
+            .collect();
+        for (x, y) in samples.iter() {
+            assert!((x - 1.0).powi(2) + y * y <= 1.0);
+        }
+        let open = samples.len() as f64 / 10000.0;
+        assert!((open - (2.0 * PI / 3.0 - 3f64.sqrt() / 2.0) / PI).abs() < 0.03);
+
+        // Nothing goes through once the barrel is beside the aperture
+        let closed = Lens {
+            cat_eye: 2.5,
+            ..Lens::default()
+        };
+        assert!((0..100).all(|_| closed.sample_aperture(1.0, 0.0).is_none()));
+    }
+}
@@ -1,16 +1,42 @@
+mod animation;
+mod aovs;
 mod camera;
+mod checkpoint;
+mod denoise;
+mod distributed;
 mod error;
+mod filters;
 mod image;
+mod lens;
+mod lights;
 mod materials;
 pub mod math;
+mod progress;
 mod ray;
+mod render;
+mod sequence;
+mod stats;
+mod textures;
 mod world;
 
 pub use self::image::*;
+pub use animation::*;
+pub use aovs::*;
 pub use camera::*;
+pub use checkpoint::*;
+pub use denoise::*;
+pub use distributed::*;
 pub use error::*;
+pub use filters::*;
+pub use lens::*;
+pub use lights::*;
 pub use materials::*;
+pub use progress::*;
 pub use ray::*;
+pub use render::*;
+pub use sequence::*;
+pub use stats::*;
+pub use textures::*;
 pub use world::*;
 
 fn clamp(x: f64, min: f64, max: f64) -> f64 {
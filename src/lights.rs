@@ -0,0 +1,292 @@
+use crate::{
+    math::{Vec3, INFINITY},
+    Color, Hittable, Ray, StableHasher,
+};
+
+// What a light sends to a given point: the unit direction towards the light, how far the light is
+// (for shadow rays) and the incoming radiance once attenuated
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub radiance: Color,
+}
+
+pub trait Light {
+    fn sample(&self, point: &Vec3) -> Option<LightSample>;
+
+    // The geometry of the light when it can be hit by rays, delta lights have none
+    fn hittable(&self) -> Option<&dyn Hittable> {
+        None
+    }
+
+    // See `Hittable::fingerprint`
+    fn fingerprint(&self, hasher: &mut StableHasher);
+}
+
+// Shapes that can pick a direction towards themselves, seen from `origin`. They return the unit
+// direction, the distance to the chosen point and the pdf of that direction (by solid angle)
+pub trait Sampleable {
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)>;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Falloff {
+    None,
+    Linear,
+    Quadratic,
+}
+
+impl Falloff {
+    fn attenuation(&self, distance: f64) -> f64 {
+        match self {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 / distance,
+            Falloff::Quadratic => 1.0 / distance.powi(2),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub intensity: Color,
+    pub falloff: Falloff,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Color, falloff: Falloff) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+            falloff,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        let attenuation = self.falloff.attenuation(distance);
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: Color::new_with_vec(attenuation * self.intensity.vec),
+        })
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+// A point light restricted to a cone around `direction`, `angle` is the half angle of the cone
+// and `softness` (between 0 and 1) the part of it over which the light fades out
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub intensity: Color,
+    pub angle: f64,
+    pub softness: f64,
+    pub falloff: Falloff,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3,
+        lookat: Vec3,
+        intensity: Color,
+        angle: f64,
+        softness: f64,
+        falloff: Falloff,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            direction: Vec3::unit(lookat - position),
+            intensity,
+            angle,
+            softness,
+            falloff,
+        }
+    }
+
+    fn cone_attenuation(&self, cos_theta: f64) -> f64 {
+        let cos_outer = self.angle.cos();
+        let cos_inner = (self.angle * (1.0 - self.softness)).cos();
+
+        if cos_theta <= cos_outer {
+            0.0
+        } else if cos_theta >= cos_inner {
+            1.0
+        } else {
+            // Smoothstep between the outer and the inner cone
+            let x = (cos_theta - cos_outer) / (cos_inner - cos_outer);
+            x * x * (3.0 - 2.0 * x)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+
+        let cone = self.cone_attenuation(Vec3::dot(&-direction, &self.direction));
+        if cone <= 0.0 {
+            return None;
+        }
+        let attenuation = cone * self.falloff.attenuation(distance);
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: Color::new_with_vec(attenuation * self.intensity.vec),
+        })
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+// A light infinitely far away (like the sun) lighting the whole scene from the same `direction`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: Color) -> DirectionalLight {
+        DirectionalLight {
+            direction: Vec3::unit(direction),
+            intensity,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: INFINITY,
+            radiance: self.intensity,
+        })
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+// Any emissive shape used as a light, its emission is the one of its material
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AreaLight<S: Hittable + Sampleable> {
+    pub shape: S,
+}
+
+impl<S: Hittable + Sampleable> AreaLight<S> {
+    pub fn new(shape: S) -> AreaLight<S> {
+        AreaLight { shape }
+    }
+
+    pub fn new_boxed(shape: S) -> Box<AreaLight<S>> {
+        Box::new(Self::new(shape))
+    }
+}
+
+impl<S: Hittable + Sampleable> Light for AreaLight<S> {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let (direction, distance, pdf) = self.shape.sample_direction(point)?;
+
+        // Find back the sampled point to know what it emits
+        let ray = Ray::new(*point, direction);
+        let hit_record = self
+            .shape
+            .hit(&ray, distance * (1.0 - 1e-6), distance * (1.0 + 1e-6))?;
+        let emitted = hit_record.material.emitted(&ray, &hit_record);
+
+        Some(LightSample {
+            direction,
+            distance: hit_record.t,
+            radiance: Color::new_with_vec(emitted.vec / pdf),
+        })
+    }
+
+    fn hittable(&self) -> Option<&dyn Hittable> {
+        Some(&self.shape)
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&"AreaLight");
+        self.shape.fingerprint(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_falloff() {
+        let light = PointLight::new(
+            Vec3::new(0.0, 2.0, 0.0),
+            Color::new(4.0, 4.0, 4.0),
+            Falloff::Quadratic,
+        );
+        let sample = light.sample(&Vec3::new(0.0, 0.0, 0.0)).unwrap();
+
+        assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.radiance, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn sphere_light_is_sampled_by_cone() {
+        use crate::{math::Sphere, DiffuseLight};
+
+        let light = AreaLight::new(Sphere::new(
+            Vec3::new(0.0, 10.0, 0.0),
+            1.0,
+            DiffuseLight::new(Color::new(1.0, 1.0, 1.0)),
+        ));
+
+        // Averaging the samples gives the solid angle of the sphere times its emission
+        let n = 10_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let sample = light.sample(&Vec3::new(0.0, 0.0, 0.0)).unwrap();
+            assert!(sample.distance >= 9.0 && sample.distance <= 10.0);
+            sum += sample.radiance.r();
+        }
+        let cos_max = (1.0 - 1.0 / 100.0_f64).sqrt();
+        let solid_angle = crate::math::TAU * (1.0 - cos_max);
+        assert!((sum / n as f64 - solid_angle).abs() < 1e-6);
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            0.5,
+            0.2,
+            Falloff::None,
+        );
+
+        let center = light.sample(&Vec3::new(0.0, 0.0, 0.0)).unwrap();
+        assert_eq!(center.radiance, Color::new(1.0, 1.0, 1.0));
+
+        // tan(0.5) is about 0.546, so this point is outside of the cone
+        assert!(light.sample(&Vec3::new(0.6, 0.0, 0.0)).is_none());
+
+        // and this one is in the soft part
+        let edge = light.sample(&Vec3::new(0.52, 0.0, 0.0)).unwrap();
+        assert!(edge.radiance.r() > 0.0 && edge.radiance.r() < 1.0);
+    }
+}
@@ -1,17 +1,125 @@
-use ray_tracer::{self, RTError};
-use std::time::Instant;
+use ray_tracer::{
+    self, Cancellation, Checkpoint, Checkpointing, CropOutput, Denoiser, Dithering, PrintProgress,
+    Progressive, RTError, RenderControl, RenderSettings,
+};
+use std::{
+    fs,
+    net::TcpListener,
+    path::Path,
+    time::{Duration, Instant},
+};
+mod options;
 mod scenes;
 
 fn main() -> Result<(), RTError> {
+    let options = options::Options::from_args()?;
+
+    if let Some((output, inputs)) = options.merge.as_ref() {
+        return merge(output, inputs);
+    }
+    if let Some(path) = options.denoise_file.as_ref() {
+        let (img, aovs, variance) = ray_tracer::read_layers_from_exr(path)?;
+        let denoised = Denoiser::new().denoise(&img, &aovs, &variance)?;
+        return ray_tracer::write_img_to_file("./target/img-denoised.jpg", &denoised);
+    }
+
+    if let Some((first, last)) = options.frames {
+        return render_frames(first, last, &options);
+    }
+
     println!("Starting...");
 
     // Create scene, empty image and other parameters
     let (img, world, camera, samples_per_pixel, depth) = scenes::random_scene_with_lights();
+    let mut settings = RenderSettings::new(samples_per_pixel, depth);
+    settings.crop = options.crop;
+
+    if let Some(address) = options.worker.as_ref() {
+        return ray_tracer::work(address, &img, &world, &camera, &settings);
+    }
+
+    // Read before rendering, a missing file is better found out now
+    let crop = match options.crop {
+        Some(window) => {
+            let output = match options.fill_from.as_ref() {
+                Some(path) => CropOutput::FillFrom(ray_tracer::read_img_from_file(path)?),
+                None => CropOutput::Window,
+            };
+            Some((window, output))
+        }
+        None => None,
+    };
 
     // Render Image
     let now = Instant::now();
-    let img = ray_tracer::create_img(img, world, samples_per_pixel, camera, depth);
+    let mut control = RenderControl::new(PrintProgress, Cancellation::new());
+    let mut aovs = None;
+    let img = if let Some(address) = options.coordinator.as_ref() {
+        // Workers are started separately with `--worker <address>`
+        let listener = TcpListener::bind(address).map_err(RTError::IO)?;
+        println!("Waiting for workers on {}", address);
+        let scene_hash = ray_tracer::scene_hash(&world, &camera);
+        let tile_size = options.tile_size.unwrap_or(32);
+        let timeout = options
+            .worker_timeout
+            .unwrap_or_else(|| Duration::from_secs(600));
+        ray_tracer::coordinate(
+            listener,
+            img,
+            scene_hash,
+            &settings,
+            tile_size,
+            timeout,
+            &mut control,
+        )?
+    } else if let Some(path) = options.checkpoint.as_ref() {
+        let every = options
+            .checkpoint_every
+            .unwrap_or_else(|| Duration::from_secs(60));
+        let checkpointing = Checkpointing::new(path, every);
+        if options.resume {
+            println!("Resuming from {}", path);
+        }
+        ray_tracer::render_with_checkpoints(
+            img,
+            &world,
+            &camera,
+            &settings,
+            &checkpointing,
+            options.resume,
+            &mut control,
+        )?
+    } else if options.progressive {
+        // Each snapshot replaces the previous one, the render can be stopped once it looks good
+        let progressive = Progressive::new(1, options.snapshot_every);
+        ray_tracer::render_progressive(
+            img,
+            &world,
+            &camera,
+            &settings,
+            &progressive,
+            &mut control,
+            &mut |img| match crop.as_ref() {
+                Some((window, output)) => ray_tracer::write_img_to_file(
+                    "./target/img-progress.jpg",
+                    &ray_tracer::apply_crop(img, window, output)?,
+                ),
+                None => ray_tracer::write_img_to_file("./target/img-progress.jpg", img),
+            },
+        )?
+    } else if options.aovs || options.denoise {
+        let (img, rendered_aovs) =
+            ray_tracer::render_with_aovs(img, &world, &camera, &settings, &mut control);
+        aovs = Some(rendered_aovs);
+        img
+    } else {
+        ray_tracer::render_with_control(img, &world, &camera, &settings, &mut control)
+    };
     let gen_time = now.elapsed().as_secs_f64();
+    let img = match crop.as_ref() {
+        Some((window, output)) => ray_tracer::apply_crop(&img, window, output)?,
+        None => img,
+    };
     println!("Image generated in {} s", gen_time);
 
     // Write to file
@@ -27,5 +135,94 @@ fn main() -> Result<(), RTError> {
     )?;
     println!("Image written in {} s", now.elapsed().as_secs_f64());
 
+    if let Some(aovs) = aovs {
+        if options.aovs {
+            ray_tracer::write_aovs_to_files("./target/img", &aovs)?;
+            ray_tracer::write_layers_to_exr("./target/img.exr", &img, &aovs)?;
+        }
+        if options.denoise {
+            let variance = ray_tracer::variance_buffer(&img);
+            let denoised = Denoiser::new().denoise(&img, &aovs, &variance)?;
+            ray_tracer::write_img_to_file("./target/img-denoised.jpg", &denoised)?;
+        }
+    }
+
+    // Rendered by the workers when coordinating, so nothing was counted here
+    if options.stats && options.coordinator.is_none() {
+        ray_tracer::write_stats_to_file("./target/img-stats.json", &control.stats)?;
+    }
+
+    Ok(())
+}
+
+// Combine checkpoints of the same scene rendered separately, then write the resulting image
+fn merge(output: &str, inputs: &[String]) -> Result<(), RTError> {
+    let checkpoints = inputs
+        .iter()
+        .map(|path| Checkpoint::load(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = Checkpoint::merge(&checkpoints)?;
+    merged.save(output)?;
+    println!("{} renders merged into {}", inputs.len(), output);
+
+    ray_tracer::write_img_to_file("./target/img.jpg", &merged.img)
+}
+
+// Render the frames of the animation to numbered files. With `--skip-existing` the frames already
+// written are kept, so that an interrupted batch can be finished
+fn render_frames(first: u32, last: u32, options: &options::Options) -> Result<(), RTError> {
+    let fps = options.fps.unwrap_or(24.0);
+
+    for frame in first..=last {
+        let path = format!("./target/frame-{:04}.png", frame);
+        if options.skip_existing && Path::new(&path).exists() {
+            println!("Frame {} already rendered", frame);
+            continue;
+        }
+
+        let (img, world, camera, samples_per_pixel, depth) =
+            scenes::turntable_scene(frame as f64 / fps);
+        let settings = RenderSettings::new(samples_per_pixel, depth);
+        let now = Instant::now();
+        let img = ray_tracer::render_with_control(
+            img,
+            &world,
+            &camera,
+            &settings,
+            &mut RenderControl::new(PrintProgress, Cancellation::new()),
+        );
+
+        // Written under another name first, a frame cut while being written is not skipped later
+        let partial = format!("./target/frame-{:04}.partial.png", frame);
+        ray_tracer::write_img_to_file(&partial, &img)?;
+        fs::rename(&partial, &path).map_err(RTError::IO)?;
+        println!(
+            "Frame {} rendered in {} s",
+            frame,
+            now.elapsed().as_secs_f64()
+        );
+    }
+
+    if options.gif.is_some() || options.apng.is_some() {
+        // Read back from the files, some frames may come from an earlier run. They are written in
+        // PNG so that nothing is lost on the way
+        let frames = (first..=last)
+            .map(|frame| {
+                ray_tracer::read_img_from_file(&format!("./target/frame-{:04}.png", frame))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(path) = options.gif.as_ref() {
+            let dithering = if options.dither {
+                Dithering::FloydSteinberg
+            } else {
+                Dithering::None
+            };
+            ray_tracer::write_gif(path, &frames, fps, dithering)?;
+        }
+        if let Some(path) = options.apng.as_ref() {
+            ray_tracer::write_apng(path, &frames, fps)?;
+        }
+    }
+
     Ok(())
 }
@@ -1,32 +1,116 @@
-use crate::{math::Vec3, Color, HitRecord, Ray};
+use crate::{
+    math::{Vec3, PI},
+    Color, HitRecord, Ray, StableHasher, Texture,
+};
 use rand::Rng;
+use std::hash::Hasher;
 
 pub trait Material {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)>;
 
-    fn emitted(&self) -> Color {
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
+
+    // Reflected part of the light coming from `direction` (unit vector), cosine term included.
+    // Only makes sense for non specular materials, the others never get lit directly by the lights
+    fn eval(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Overall color of the surface, for the albedo buffer
+    fn albedo(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Specular materials are not lit by `eval`, they only see the lights through `scatter`
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    // Composite materials return the sub material that should handle this hit
+    fn pick(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<&dyn Material> {
+        None
+    }
+
+    // See `Hittable::fingerprint`
+    fn fingerprint(&self, hasher: &mut StableHasher);
+
+    // Identifies the material in the material ID buffer, from its description: materials made the
+    // same way share it from one render to another. 0 is left for the background
+    fn id(&self) -> u32 {
+        let mut hasher = StableHasher::new();
+        self.fingerprint(&mut hasher);
+        (hasher.finish() as u32).max(1)
+    }
 }
 
+impl dyn Material + '_ {
+    // Follow the picks down to a material that does not delegate anymore
+    pub fn resolve(&self, ray_in: &Ray, hit_record: &HitRecord) -> &dyn Material {
+        let mut material = self;
+        while let Some(picked) = material.pick(ray_in, hit_record) {
+            material = picked;
+        }
+        material
+    }
+}
+
+// Use Schlick's approximation for reflectance.
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    let r0 = r0.powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+// Emits `emit` (a plain color or any texture), from its front face only if not `two_sided`
 #[derive(Debug, PartialEq, Clone, Copy)]
-pub struct DiffuseLight {
-    pub emit: Color,
+pub struct DiffuseLight<T: Texture = Color> {
+    pub emit: T,
+    pub two_sided: bool,
+}
+
+impl<T: Texture> DiffuseLight<T> {
+    pub fn new(emit: T) -> DiffuseLight<T> {
+        DiffuseLight {
+            emit,
+            two_sided: true,
+        }
+    }
+
+    pub fn new_one_sided(emit: T) -> DiffuseLight<T> {
+        DiffuseLight {
+            emit,
+            two_sided: false,
+        }
+    }
 }
 
 impl DiffuseLight {
-    pub fn new(emit: Color) -> DiffuseLight {
-        DiffuseLight { emit }
+    // Light of a black body at `kelvin` degrees, `intensity` being its luminance
+    pub fn new_blackbody(kelvin: f64, intensity: f64) -> DiffuseLight {
+        DiffuseLight::new(Color::new_with_vec(
+            intensity * Color::new_blackbody(kelvin).vec,
+        ))
     }
 }
 
-impl Material for DiffuseLight {
+impl<T: Texture> Material for DiffuseLight<T> {
     fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<(Ray, Color)> {
         None
     }
 
-    fn emitted(&self) -> Color {
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        if !self.two_sided && !hit_record.front_face {
+            return Color::new(0.0, 0.0, 0.0);
+        }
         self.emit
+            .value(hit_record.u, hit_record.v, &hit_record.point)
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("DiffuseLight", self.two_sided));
+        self.emit.fingerprint(hasher);
     }
 }
 
@@ -48,6 +132,23 @@ impl Material for Lambertian {
         let attenuation = self.albedo;
         Some((scattered, attenuation))
     }
+
+    fn eval(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let cosine = f64::max(Vec3::dot(&hit_record.normal, direction), 0.0);
+        Color::new_with_vec(cosine / PI * self.albedo.vec)
+    }
+
+    fn albedo(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -76,6 +177,14 @@ impl Material for Metal {
             None
         }
     }
+
+    fn albedo(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -92,13 +201,6 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
-        fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-            // Use Schlick's approximation for reflectance.
-            let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-            let r0 = r0.powi(2);
-            r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
-        }
-
         let refraction_ratio = if hit_record.front_face {
             1.0 / self.ir
         } else {
@@ -123,4 +225,267 @@ impl Material for Dielectric {
 
         Some((scattered, self.albedo))
     }
+
+    fn albedo(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+// Chooses between two materials, `weight` is the probability to use `b` instead of `a`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Mix<A: Material, B: Material, T: Texture> {
+    pub a: A,
+    pub b: B,
+    pub weight: T,
+}
+
+impl<A: Material, B: Material, T: Texture> Mix<A, B, T> {
+    pub fn new(a: A, b: B, weight: T) -> Mix<A, B, T> {
+        Mix { a, b, weight }
+    }
+
+    fn weight(&self, hit_record: &HitRecord) -> f64 {
+        let weight = self
+            .weight
+            .value(hit_record.u, hit_record.v, &hit_record.point);
+        (weight.r() + weight.g() + weight.b()) / 3.0
+    }
+}
+
+impl<A: Material, B: Material, T: Texture> Material for Mix<A, B, T> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        (self as &dyn Material)
+            .resolve(ray_in, hit_record)
+            .scatter(ray_in, hit_record)
+    }
+
+    // Both materials blended like the albedo, for when the mix is not resolved first
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let weight = self.weight(hit_record);
+        Color::new_with_vec(
+            (1.0 - weight) * self.a.emitted(ray_in, hit_record).vec
+                + weight * self.b.emitted(ray_in, hit_record).vec,
+        )
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let weight = self.weight(hit_record);
+        Color::new_with_vec(
+            (1.0 - weight) * self.a.eval(ray_in, hit_record, direction).vec
+                + weight * self.b.eval(ray_in, hit_record, direction).vec,
+        )
+    }
+
+    fn albedo(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let weight = self.weight(hit_record);
+        Color::new_with_vec(
+            (1.0 - weight) * self.a.albedo(ray_in, hit_record).vec
+                + weight * self.b.albedo(ray_in, hit_record).vec,
+        )
+    }
+
+    // Lit by the lights as soon as one of the two can be
+    fn is_specular(&self) -> bool {
+        self.a.is_specular() && self.b.is_specular()
+    }
+
+    fn pick(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<&dyn Material> {
+        let weight = self.weight(hit_record);
+
+        if rand::thread_rng().gen::<f64>() < weight {
+            Some(&self.b)
+        } else {
+            Some(&self.a)
+        }
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&"Mix");
+        self.a.fingerprint(hasher);
+        self.b.fingerprint(hasher);
+        self.weight.fingerprint(hasher);
+    }
+}
+
+// A clear coat (varnish, lacquer...) over a base material: the coat reflects following the Fresnel
+// term of a dielectric of index `ir`, everything else goes through to the base
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Layered<C: Material, B: Material> {
+    pub coat: C,
+    pub base: B,
+    pub ir: f64,
+}
+
+impl<C: Material, B: Material> Layered<C, B> {
+    pub fn new(coat: C, base: B, ir: f64) -> Layered<C, B> {
+        Layered { coat, base, ir }
+    }
+}
+
+impl<C: Material, B: Material> Material for Layered<C, B> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        (self as &dyn Material)
+            .resolve(ray_in, hit_record)
+            .scatter(ray_in, hit_record)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.base.emitted(ray_in, hit_record)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        self.base.eval(ray_in, hit_record, direction)
+    }
+
+    // The coat is clear, what is seen is the base
+    fn albedo(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.base.albedo(ray_in, hit_record)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn pick(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<&dyn Material> {
+        // The coat is only seen from the outside
+        if !hit_record.front_face {
+            return Some(&self.base);
+        }
+
+        let unit_direction = Vec3::unit(ray_in.direction);
+        let cos_theta = f64::min(Vec3::dot(&-unit_direction, &hit_record.normal), 1.0);
+
+        if reflectance(cos_theta, 1.0 / self.ir) > rand::thread_rng().gen::<f64>() {
+            Some(&self.coat)
+        } else {
+            Some(&self.base)
+        }
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Layered", self.ir));
+        self.coat.fingerprint(hasher);
+        self.base.fingerprint(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_picks_by_weight() {
+        let lambertian = Lambertian::new(Color::new(1.0, 0.0, 0.0));
+        let light = DiffuseLight::new(Color::new(4.0, 4.0, 4.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let mix = Mix::new(lambertian, light, 0.0);
+        let hit_record = HitRecord::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            true,
+            (0.5, 0.5),
+            &mix,
+        );
+        let material = hit_record.material.resolve(&ray, &hit_record);
+        assert_eq!(
+            material.emitted(&ray, &hit_record),
+            Color::new(0.0, 0.0, 0.0)
+        );
+        assert!(material.scatter(&ray, &hit_record).is_some());
+
+        let mix = Mix::new(lambertian, light, Color::new(1.0, 1.0, 1.0));
+        let hit_record = HitRecord::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            true,
+            (0.5, 0.5),
+            &mix,
+        );
+        let material = hit_record.material.resolve(&ray, &hit_record);
+        assert_eq!(
+            material.emitted(&ray, &hit_record),
+            Color::new(4.0, 4.0, 4.0)
+        );
+        assert!(material.scatter(&ray, &hit_record).is_none());
+
+        let mix = Mix::new(lambertian, light, Color::new(0.25, 0.25, 0.25));
+        let hit_record = HitRecord::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            true,
+            (0.5, 0.5),
+            &mix,
+        );
+        assert_eq!(mix.emitted(&ray, &hit_record), Color::new(1.0, 1.0, 1.0));
+        // Three quarters of the lambertian, the light reflects nothing
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let eval = mix.eval(&ray, &hit_record, &up).vec;
+        assert!((eval - 0.75 * lambertian.eval(&ray, &hit_record, &up).vec).length() < 1e-12);
+        assert!(!mix.is_specular());
+        assert!(Mix::new(light, light, 0.5).is_specular());
+    }
+
+    #[test]
+    fn one_sided_light() {
+        let light = DiffuseLight::new_one_sided(Color::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = |front_face| {
+            HitRecord::new(
+                Vec3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                1.0,
+                front_face,
+                (0.5, 0.5),
+                &light,
+            )
+        };
+
+        assert_eq!(light.emitted(&ray, &hit(true)), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(light.emitted(&ray, &hit(false)), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn blackbody_light() {
+        let white = DiffuseLight::new_blackbody(6500.0, 2.0).emit;
+        assert!((white.r() - 2.0).abs() < 0.2);
+        assert!((white.g() - 2.0).abs() < 0.2);
+        assert!((white.b() - 2.0).abs() < 0.2);
+
+        let candle = DiffuseLight::new_blackbody(1900.0, 1.0).emit;
+        assert!(candle.r() > candle.g() && candle.g() > candle.b());
+    }
+
+    #[test]
+    fn layered_uses_base_from_inside() {
+        let coat = Metal::new(Color::new(1.0, 1.0, 1.0), 0.0);
+        let base = DiffuseLight::new(Color::new(2.0, 2.0, 2.0));
+        let layered = Layered::new(coat, base, 1.5);
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = HitRecord::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            false,
+            (0.5, 0.5),
+            &layered,
+        );
+
+        assert!(layered.is_specular());
+        assert!(!Layered::new(coat, Lambertian::new(Color::new(0.5, 0.5, 0.5)), 1.5).is_specular());
+        for _ in 0..100 {
+            let material = hit_record.material.resolve(&ray, &hit_record);
+            assert_eq!(
+                material.emitted(&ray, &hit_record),
+                Color::new(2.0, 2.0, 2.0)
+            );
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use super::Vec3;
+use crate::Ray;
+
+// Axis-aligned bounding box
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Aabb {
+            min: Vec3::min(&a, &b),
+            max: Vec3::max(&a, &b),
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vec3::min(&self.min, &other.min),
+            max: Vec3::max(&self.max, &other.max),
+        }
+    }
+
+    // Box around a disk of `radius` centered on `center` and facing the unit vector `normal`
+    pub fn around_disk(center: Vec3, normal: Vec3, radius: f64) -> Aabb {
+        let extent = radius
+            * Vec3::new(
+                (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+                (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+                (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+            );
+        Aabb::new(center - extent, center + extent)
+    }
+
+    // Slab test: does the ray go through the box between `t_min` and `t_max`
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        let axes = [
+            (r.origin.x, r.direction.x, self.min.x, self.max.x),
+            (r.origin.y, r.direction.y, self.min.y, self.max.y),
+            (r.origin.z, r.direction.z, self.min.z, self.max.z),
+        ];
+        let (mut t_min, mut t_max) = (t_min, t_max);
+        for (origin, direction, min, max) in axes.iter() {
+            let inverse = 1.0 / direction;
+            let (t0, t1) = ((min - origin) * inverse, (max - origin) * inverse);
+            let (t0, t1) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
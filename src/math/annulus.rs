@@ -0,0 +1,117 @@
+use super::{outward_hit, turn_fraction, Aabb, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, StableHasher};
+
+// Flat ring between `inner_radius` and `outer_radius` around `center`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Annulus<M: Material> {
+    center: Vec3,
+    normal: Vec3,
+    inner_radius: f64,
+    outer_radius: f64,
+    material: M,
+}
+
+impl<M: Material> Annulus<M> {
+    pub fn new(
+        center: Vec3,
+        normal: Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        material: M,
+    ) -> Self {
+        Annulus {
+            center,
+            normal: Vec3::unit(normal),
+            inner_radius,
+            outer_radius,
+            material,
+        }
+    }
+
+    pub fn new_boxed(
+        center: Vec3,
+        normal: Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        material: M,
+    ) -> Box<Self> {
+        Box::new(Self::new(
+            center,
+            normal,
+            inner_radius,
+            outer_radius,
+            material,
+        ))
+    }
+}
+
+impl<M: Material> Hittable for Annulus<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let denom = Vec3::dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+
+        let t = Vec3::dot(&self.normal, &(self.center - r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let p = r.at(t) - self.center;
+        let distance = p.length();
+        if distance < self.inner_radius || distance > self.outer_radius {
+            return None;
+        }
+
+        // Around the ring, then from its inner edge to its outer one
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.normal);
+        let uv = (
+            turn_fraction(&p, &u_axis, &v_axis),
+            (distance - self.inner_radius) / (self.outer_radius - self.inner_radius),
+        );
+
+        Some(outward_hit(r, t, self.normal, uv, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around_disk(
+            self.center,
+            self.normal,
+            self.outer_radius,
+        ))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&(
+            "Annulus",
+            self.center,
+            self.normal,
+            self.inner_radius,
+            self.outer_radius,
+        ));
+        self.material.fingerprint(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::check_hit, Color, Lambertian};
+
+    #[test]
+    fn annulus_has_a_hole() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let annulus = Annulus::new(Vec3::new(0.0, 0.0, 0.0), y, 1.0, 2.0, material);
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), -y);
+        assert!(annulus.hit(&down, 0.001, f64::INFINITY).is_none());
+        check_hit(
+            &annulus,
+            &Ray::new(Vec3::new(1.5, 5.0, 0.0), -y),
+            5.0,
+            y,
+            true,
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use super::{first_hit, outward_hit, sort_hits, turn_fraction, Aabb, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Solid, StableHasher};
+
+// Cone closed by its base, a disk of `radius` around `base`, with its apex `height` further along
+// `axis`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cone<M: Material> {
+    base: Vec3,
+    axis: Vec3,
+    radius: f64,
+    height: f64,
+    material: M,
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(base: Vec3, axis: Vec3, radius: f64, height: f64, material: M) -> Self {
+        Cone {
+            base,
+            axis: Vec3::unit(axis),
+            radius,
+            height,
+            material,
+        }
+    }
+
+    pub fn new_boxed(base: Vec3, axis: Vec3, radius: f64, height: f64, material: M) -> Box<Self> {
+        Box::new(Self::new(base, axis, radius, height, material))
+    }
+}
+
+impl<M: Material> Hittable for Cone<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_hit(self.intersections(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let apex = self.base + self.height * self.axis;
+        Some(
+            Aabb::around_disk(self.base, self.axis, self.radius)
+                .surrounding(&Aabb::new(apex, apex)),
+        )
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Cone", self.base, self.axis, self.radius, self.height));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Solid for Cone<M> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let o = r.origin - self.base;
+        let (o_axial, d_axial) = (
+            Vec3::dot(&o, &self.axis),
+            Vec3::dot(&r.direction, &self.axis),
+        );
+        let o_radial = o - o_axial * self.axis;
+        let d_radial = r.direction - d_axial * self.axis;
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.axis);
+        let mut hits = vec![];
+
+        // The radius at the height h is k (height - h)
+        let k2 = (self.radius / self.height).powi(2);
+        let below_apex = self.height - o_axial;
+        let a = d_radial.length_squared() - k2 * d_axial.powi(2);
+        let half_b = Vec3::dot(&o_radial, &d_radial) + k2 * below_apex * d_axial;
+        let c = o_radial.length_squared() - k2 * below_apex.powi(2);
+        let roots = if a.abs() > 1e-12 {
+            let discriminant = half_b.powi(2) - a * c;
+            if discriminant >= 0.0 {
+                let root = discriminant.sqrt();
+                vec![(-half_b - root) / a, (-half_b + root) / a]
+            } else {
+                vec![]
+            }
+        } else if half_b.abs() > 1e-12 {
+            // Parallel to the side of the cone: a single crossing
+            vec![-c / (2.0 * half_b)]
+        } else {
+            vec![]
+        };
+        for t in roots {
+            let h = o_axial + t * d_axial;
+            if (0.0..=self.height).contains(&h) {
+                let radial = o_radial + t * d_radial;
+                let outward = if radial.length_squared() > 0.0 {
+                    Vec3::unit(self.height * Vec3::unit(radial) + self.radius * self.axis)
+                } else {
+                    self.axis
+                };
+                let uv = (turn_fraction(&radial, &u_axis, &v_axis), h / self.height);
+                hits.push(outward_hit(r, t, outward, uv, &self.material));
+            }
+        }
+
+        if d_axial.abs() > 1e-12 {
+            let t = -o_axial / d_axial;
+            let radial = o_radial + t * d_radial;
+            if radial.length_squared() <= self.radius.powi(2) {
+                let uv = (
+                    turn_fraction(&radial, &u_axis, &v_axis),
+                    radial.length() / self.radius,
+                );
+                hits.push(outward_hit(r, t, -self.axis, uv, &self.material));
+            }
+        }
+
+        sort_hits(&mut hits);
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::check_hit, Color, Lambertian};
+
+    // Side at 45° from the tip
+    #[test]
+    fn cone_is_hit_on_tip_side_and_base() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let (x, y) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let cone = Cone::new(Vec3::new(0.0, 0.0, 0.0), y, 1.0, 1.0, material);
+        check_hit(&cone, &Ray::new(Vec3::new(0.0, 5.0, 0.0), -y), 4.0, y, true);
+        check_hit(
+            &cone,
+            &Ray::new(Vec3::new(-5.0, 0.5, 0.0), x),
+            4.5,
+            Vec3::unit(y - x),
+            true,
+        );
+        check_hit(
+            &cone,
+            &Ray::new(Vec3::new(0.0, -5.0, 0.0), y),
+            5.0,
+            -y,
+            true,
+        );
+    }
+}
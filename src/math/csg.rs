@@ -0,0 +1,190 @@
+use super::{first_hit, Aabb};
+use crate::{HitRecord, Hittable, Ray, Solid, StableHasher};
+
+// How the insides of two solids are combined
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // The left solid carved by the right one
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// Boolean combination of two solids, itself a solid so that they can be nested. Each part of its
+// surface keeps the material of the solid it comes from
+pub struct Csg {
+    pub operation: CsgOperation,
+    left: Box<dyn Solid>,
+    right: Box<dyn Solid>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn new_boxed(
+        operation: CsgOperation,
+        left: Box<dyn Solid>,
+        right: Box<dyn Solid>,
+    ) -> Box<Self> {
+        Box::new(Self::new(operation, left, right))
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_hit(self.intersections(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.operation {
+            CsgOperation::Union => Some(
+                self.left
+                    .bounding_box()?
+                    .surrounding(&self.right.bounding_box()?),
+            ),
+            // Never larger than the left solid
+            CsgOperation::Intersection | CsgOperation::Difference => self.left.bounding_box(),
+        }
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Csg", self.operation));
+        self.left.fingerprint(hasher);
+        self.right.fingerprint(hasher);
+    }
+}
+
+impl Solid for Csg {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let left = self.left.intersections(r);
+        let right = self.right.intersections(r);
+
+        // Far behind the ray, each solid is entered by its first crossing unless the line starts in it
+        let mut in_left = left.first().is_some_and(|hit| !hit.front_face);
+        let mut in_right = right.first().is_some_and(|hit| !hit.front_face);
+        let mut inside = self.operation.inside(in_left, in_right);
+
+        let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+        let mut hits = vec![];
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut hit = if from_left {
+                in_left = !in_left;
+                left.next().unwrap()
+            } else {
+                in_right = !in_right;
+                right.next().unwrap()
+            };
+
+            // Only the crossings where the result goes in or out are on its surface. The normal
+            // already faces the ray, only the side it is on changes
+            let now_inside = self.operation.inside(in_left, in_right);
+            if now_inside != inside {
+                hit.front_face = now_inside;
+                hits.push(hit);
+                inside = now_inside;
+            }
+        }
+
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{Cylinder, Sphere, Vec3},
+        Color, DiffuseLight,
+    };
+
+    #[test]
+    fn carved_sphere() {
+        let carve = |operation| {
+            Csg::new(
+                operation,
+                Sphere::new_boxed(
+                    Vec3::new(0.0, 0.0, 0.0),
+                    1.0,
+                    DiffuseLight::new(Color::new(1.0, 0.0, 0.0)),
+                ),
+                Cylinder::new_boxed(
+                    Vec3::new(0.0, -2.0, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    0.3,
+                    4.0,
+                    DiffuseLight::new(Color::new(0.0, 0.0, 1.0)),
+                ),
+            )
+        };
+        let color = |r: &Ray, hit: &HitRecord| hit.material.emitted(r, hit);
+        let (red, blue) = (Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0));
+
+        // Through the hole: nothing left to hit
+        let holed = carve(CsgOperation::Difference);
+        let down = Ray::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(holed.hit(&down, 0.001, f64::INFINITY).is_none());
+
+        // Across it: in the sphere, out into the hole, back in and out of the sphere
+        let across = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = holed.intersections(&across);
+        let expected = [
+            (2.0, true, red),
+            (2.7, false, blue),
+            (3.3, true, blue),
+            (4.0, false, red),
+        ];
+        assert_eq!(hits.len(), expected.len());
+        for (hit, (t, front_face, c)) in hits.iter().zip(expected.iter()) {
+            assert!((hit.t - t).abs() < 1e-9);
+            assert_eq!(hit.front_face, *front_face);
+            assert!(Vec3::dot(&hit.normal, &across.direction) < 0.0);
+            assert_eq!(color(&across, hit), *c);
+        }
+
+        // From inside the carved part the first surface is the wall of the hole, seen from inside
+        let inside = Ray::new(Vec3::new(-0.6, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = holed.hit(&inside, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 0.3).abs() < 1e-9 && !hit.front_face);
+
+        let core = carve(CsgOperation::Intersection);
+        let hits = core.intersections(&across);
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].t - 2.7).abs() < 1e-9 && hits[0].front_face);
+        assert_eq!(color(&across, &hits[0]), blue);
+        assert!(core.hit(&down, 0.001, f64::INFINITY).is_some());
+
+        // The cylinder sticks out of the sphere at both ends
+        let union = carve(CsgOperation::Union);
+        let hits = union.intersections(&down);
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].t - 1.0).abs() < 1e-9 && hits[0].front_face);
+        assert_eq!(color(&down, &hits[0]), blue);
+        assert_eq!(union.intersections(&across).len(), 2);
+
+        let bbox = holed.bounding_box().unwrap();
+        assert_eq!(bbox.max, Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(union.bounding_box().unwrap().max.y, 2.0);
+    }
+}
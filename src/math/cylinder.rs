@@ -0,0 +1,135 @@
+use super::{first_hit, outward_hit, sort_hits, turn_fraction, Aabb, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Solid, StableHasher};
+
+// Cylinder of `radius` closed at both ends, going from the center of its base `base` for `height`
+// along `axis`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cylinder<M: Material> {
+    base: Vec3,
+    axis: Vec3,
+    radius: f64,
+    height: f64,
+    material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(base: Vec3, axis: Vec3, radius: f64, height: f64, material: M) -> Self {
+        Cylinder {
+            base,
+            axis: Vec3::unit(axis),
+            radius,
+            height,
+            material,
+        }
+    }
+
+    pub fn new_boxed(base: Vec3, axis: Vec3, radius: f64, height: f64, material: M) -> Box<Self> {
+        Box::new(Self::new(base, axis, radius, height, material))
+    }
+}
+
+impl<M: Material> Hittable for Cylinder<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_hit(self.intersections(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.base + self.height * self.axis;
+        Some(
+            Aabb::around_disk(self.base, self.axis, self.radius).surrounding(&Aabb::around_disk(
+                top,
+                self.axis,
+                self.radius,
+            )),
+        )
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Cylinder", self.base, self.axis, self.radius, self.height));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Solid for Cylinder<M> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        stats::count_intersection_test();
+        // Along the axis and across it, from the base
+        let o = r.origin - self.base;
+        let (o_axial, d_axial) = (
+            Vec3::dot(&o, &self.axis),
+            Vec3::dot(&r.direction, &self.axis),
+        );
+        let o_radial = o - o_axial * self.axis;
+        let d_radial = r.direction - d_axial * self.axis;
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.axis);
+        let mut hits = vec![];
+
+        let a = d_radial.length_squared();
+        let half_b = Vec3::dot(&o_radial, &d_radial);
+        let c = o_radial.length_squared() - self.radius.powi(2);
+        let discriminant = half_b.powi(2) - a * c;
+        if a > 1e-12 && discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            for t in [(-half_b - root) / a, (-half_b + root) / a].iter() {
+                let h = o_axial + t * d_axial;
+                if (0.0..=self.height).contains(&h) {
+                    let radial = (o_radial + *t * d_radial) / self.radius;
+                    let uv = (turn_fraction(&radial, &u_axis, &v_axis), h / self.height);
+                    hits.push(outward_hit(r, *t, radial, uv, &self.material));
+                }
+            }
+        }
+
+        if d_axial.abs() > 1e-12 {
+            for (h, normal) in [(0.0, -self.axis), (self.height, self.axis)].iter() {
+                let t = (h - o_axial) / d_axial;
+                let radial = o_radial + t * d_radial;
+                if radial.length_squared() <= self.radius.powi(2) {
+                    let uv = (
+                        turn_fraction(&radial, &u_axis, &v_axis),
+                        radial.length() / self.radius,
+                    );
+                    hits.push(outward_hit(r, t, *normal, uv, &self.material));
+                }
+            }
+        }
+
+        sort_hits(&mut hits);
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::check_hit, Color, Lambertian};
+
+    // Along its axis a cylinder is hit on its caps, across it on its side, and from inside too
+    #[test]
+    fn cylinder_is_hit_on_caps_and_side() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let (x, y) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let cylinder = Cylinder::new(Vec3::new(0.0, 0.0, 0.0), y, 1.0, 2.0, material);
+        check_hit(
+            &cylinder,
+            &Ray::new(Vec3::new(0.0, 5.0, 0.0), -y),
+            3.0,
+            y,
+            true,
+        );
+        check_hit(
+            &cylinder,
+            &Ray::new(Vec3::new(-5.0, 0.5, 0.0), x),
+            4.0,
+            -x,
+            true,
+        );
+        check_hit(
+            &cylinder,
+            &Ray::new(Vec3::new(0.0, 0.5, 0.0), x),
+            1.0,
+            -x,
+            false,
+        );
+    }
+}
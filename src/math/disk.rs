@@ -0,0 +1,104 @@
+use super::{rect::area_sample_to_direction, Aabb, Vec3, PI, TAU};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Sampleable, StableHasher};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Disk<M: Material> {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    material: M,
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: M) -> Self {
+        Disk {
+            center,
+            normal: Vec3::unit(normal),
+            radius,
+            material,
+        }
+    }
+
+    pub fn new_boxed(center: Vec3, normal: Vec3, radius: f64, material: M) -> Box<Self> {
+        Box::new(Self::new(center, normal, radius, material))
+    }
+}
+
+impl<M: Material> Hittable for Disk<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let denom = Vec3::dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(&self.normal, &(self.center - r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let point = r.at(t);
+        let p = point - self.center;
+        let distance_squared = p.length_squared();
+        if distance_squared > self.radius.powi(2) {
+            return None;
+        }
+
+        // Polar coordinates on the disk
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.normal);
+        let phi = Vec3::dot(&p, &v_axis).atan2(Vec3::dot(&p, &u_axis)) + PI;
+        let uv = (phi / TAU, distance_squared.sqrt() / self.radius);
+
+        let front_face = denom < 0.0;
+        let normal = if front_face {
+            self.normal
+        } else {
+            -self.normal
+        };
+
+        Some(HitRecord::new(
+            point,
+            normal,
+            t,
+            front_face,
+            uv,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around_disk(self.center, self.normal, self.radius))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Disk", self.center, self.normal, self.radius));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Sampleable for Disk<M> {
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.normal);
+        let d = Vec3::new_random_in_unit_disk();
+        let point = self.center + self.radius * (d.x * u_axis + d.y * v_axis);
+
+        area_sample_to_direction(origin, &point, &self.normal, PI * self.radius.powi(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Lambertian};
+
+    #[test]
+    fn disk_excludes_the_bounds_of_the_interval() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let disk = Disk::new(Vec3::new(0.0, 0.0, 0.0), y, 1.0, material);
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), -y);
+        assert!((disk.hit(&down, 0.001, f64::INFINITY).unwrap().t - 5.0).abs() < 1e-9);
+        assert!(disk.hit(&down, 5.0, f64::INFINITY).is_none());
+        assert!(disk.hit(&down, 0.001, 5.0).is_none());
+    }
+}
@@ -1,11 +1,89 @@
+mod aabb;
+mod annulus;
+mod cone;
+mod csg;
+mod cylinder;
+mod disk;
+mod plane;
+mod rect;
 mod sphere;
+mod torus;
+mod triangle;
 mod vec3;
 
+pub use aabb::*;
+pub use annulus::*;
+pub use cone::*;
+pub use csg::*;
+pub use cylinder::*;
+pub use disk::*;
+pub use plane::*;
+pub use rect::*;
 pub use sphere::*;
+pub use torus::*;
+pub use triangle::*;
 pub use vec3::*;
 
+use crate::{HitRecord, Material, Ray};
+
 pub const PI: f64 = std::f64::consts::PI;
 pub const INFINITY: f64 = f64::INFINITY;
 pub const NEG_INFINITY: f64 = f64::NEG_INFINITY;
 
 pub const TAU: f64 = 2.0 * PI;
+
+// Hit record with the normal turned toward the ray, `outward_normal` being the unit normal pointing
+// out of the surface
+pub(crate) fn outward_hit<'a>(
+    r: &Ray,
+    t: f64,
+    outward_normal: Vec3,
+    uv: (f64, f64),
+    material: &'a dyn Material,
+) -> HitRecord<'a> {
+    let front_face = Vec3::dot(&r.direction, &outward_normal) < 0.0;
+    let normal = if front_face {
+        outward_normal
+    } else {
+        -outward_normal
+    };
+    HitRecord::new(r.at(t), normal, t, front_face, uv, material)
+}
+
+// Nearest of the crossings of the surface, sorted along the ray, between `t_min` and `t_max`
+pub(crate) fn first_hit(hits: Vec<HitRecord<'_>>, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+    hits.into_iter().find(|hit| hit.t > t_min && hit.t < t_max)
+}
+
+// Angle of `p` around an axis in the plane of `u_axis` and `v_axis`, mapped to [0, 1]
+pub(crate) fn turn_fraction(p: &Vec3, u_axis: &Vec3, v_axis: &Vec3) -> f64 {
+    (Vec3::dot(p, v_axis).atan2(Vec3::dot(p, u_axis)) + PI) / TAU
+}
+
+pub(crate) fn sort_hits(hits: &mut [HitRecord<'_>]) {
+    hits.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+// Check the first hit of `r` on `object` and that it lies within its bounding box
+#[cfg(test)]
+pub(crate) fn check_hit(
+    object: &dyn crate::Hittable,
+    r: &Ray,
+    t: f64,
+    normal: Vec3,
+    front_face: bool,
+) {
+    let hit = object.hit(r, 0.001, f64::INFINITY).unwrap();
+    assert!((hit.t - t).abs() < 1e-9, "t {} instead of {}", hit.t, t);
+    assert!((hit.normal - normal).length() < 1e-9);
+    assert_eq!(hit.front_face, front_face);
+    assert!((0.0..=1.0).contains(&hit.u) && (0.0..=1.0).contains(&hit.v));
+    if let Some(bbox) = object.bounding_box() {
+        assert!(bbox.hit(r, 0.001, f64::INFINITY));
+        let p = hit.point;
+        let inside = |min: f64, x: f64, max: f64| min - 1e-9 <= x && x <= max + 1e-9;
+        assert!(inside(bbox.min.x, p.x, bbox.max.x));
+        assert!(inside(bbox.min.y, p.y, bbox.max.y));
+        assert!(inside(bbox.min.z, p.z, bbox.max.z));
+    }
+}
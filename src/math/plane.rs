@@ -0,0 +1,74 @@
+use super::{outward_hit, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, StableHasher};
+
+// Infinite plane going through `point`. Its texture coordinates repeat every unit of length
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Plane<M: Material> {
+    point: Vec3,
+    normal: Vec3,
+    material: M,
+}
+
+impl<M: Material> Plane<M> {
+    pub fn new(point: Vec3, normal: Vec3, material: M) -> Self {
+        Plane {
+            point,
+            normal: Vec3::unit(normal),
+            material,
+        }
+    }
+
+    pub fn new_boxed(point: Vec3, normal: Vec3, material: M) -> Box<Self> {
+        Box::new(Self::new(point, normal, material))
+    }
+}
+
+impl<M: Material> Hittable for Plane<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let denom = Vec3::dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+
+        let t = Vec3::dot(&self.normal, &(self.point - r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let p = r.at(t) - self.point;
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.normal);
+        let uv = (
+            Vec3::dot(&p, &u_axis).rem_euclid(1.0),
+            Vec3::dot(&p, &v_axis).rem_euclid(1.0),
+        );
+
+        Some(outward_hit(r, t, self.normal, uv, &self.material))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Plane", self.point, self.normal));
+        self.material.fingerprint(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::check_hit, Color, Lambertian};
+
+    #[test]
+    fn plane_is_hit_from_both_sides() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), y, material);
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), -y);
+        check_hit(&plane, &down, 5.0, y, true);
+        assert!(plane.bounding_box().is_none());
+        check_hit(&plane, &Ray::new(-y, y), 1.0, -y, false);
+
+        // The bounds of the interval are excluded, like for the other shapes
+        assert!(plane.hit(&down, 5.0, f64::INFINITY).is_none());
+        assert!(plane.hit(&down, 0.001, 5.0).is_none());
+    }
+}
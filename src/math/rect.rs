@@ -0,0 +1,138 @@
+use super::{Aabb, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Sampleable, StableHasher};
+use rand::Rng;
+
+// A parallelogram spanned by `edge_u` and `edge_v` from `corner`, a rectangle when they are perpendicular
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Rect<M: Material> {
+    corner: Vec3,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    normal: Vec3,
+    area: f64,
+    material: M,
+}
+
+impl<M: Material> Rect<M> {
+    pub fn new(corner: Vec3, edge_u: Vec3, edge_v: Vec3, material: M) -> Self {
+        let n = Vec3::cross(&edge_u, &edge_v);
+        Rect {
+            corner,
+            edge_u,
+            edge_v,
+            normal: Vec3::unit(n),
+            area: n.length(),
+            material,
+        }
+    }
+
+    pub fn new_boxed(corner: Vec3, edge_u: Vec3, edge_v: Vec3, material: M) -> Box<Self> {
+        Box::new(Self::new(corner, edge_u, edge_v, material))
+    }
+}
+
+impl<M: Material> Hittable for Rect<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let denom = Vec3::dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(&self.normal, &(self.corner - r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        // Express the hit point in the (edge_u, edge_v) coordinates
+        let point = r.at(t);
+        let p = point - self.corner;
+        let n = Vec3::cross(&self.edge_u, &self.edge_v);
+        let w = n / n.length_squared();
+        let alpha = Vec3::dot(&w, &Vec3::cross(&p, &self.edge_v));
+        let beta = Vec3::dot(&w, &Vec3::cross(&self.edge_u, &p));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let front_face = denom < 0.0;
+        let normal = if front_face {
+            self.normal
+        } else {
+            -self.normal
+        };
+
+        Some(HitRecord::new(
+            point,
+            normal,
+            t,
+            front_face,
+            (alpha, beta),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let opposite = self.corner + self.edge_u + self.edge_v;
+        Some(Aabb::new(self.corner, opposite).surrounding(&Aabb::new(
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+        )))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Rect", self.corner, self.edge_u, self.edge_v));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Sampleable for Rect<M> {
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let mut rng = rand::thread_rng();
+        let point = self.corner
+            + rng.gen_range(0.0..1.0) * self.edge_u
+            + rng.gen_range(0.0..1.0) * self.edge_v;
+
+        area_sample_to_direction(origin, &point, &self.normal, self.area)
+    }
+}
+
+// Turn a point uniformly picked on a surface into a direction from `origin` with its solid angle pdf
+pub(crate) fn area_sample_to_direction(
+    origin: &Vec3,
+    point: &Vec3,
+    normal: &Vec3,
+    area: f64,
+) -> Option<(Vec3, f64, f64)> {
+    let to_point = *point - *origin;
+    let distance_squared = to_point.length_squared();
+    let distance = distance_squared.sqrt();
+    let direction = to_point / distance;
+    let cosine = Vec3::dot(&direction, normal).abs();
+    if cosine < 1e-8 {
+        return None;
+    }
+
+    Some((direction, distance, distance_squared / (cosine * area)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Lambertian};
+
+    #[test]
+    fn rect_excludes_the_bounds_of_the_interval() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let rect = Rect::new(
+            Vec3::new(-1.0, 0.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            material,
+        );
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!((rect.hit(&down, 0.001, f64::INFINITY).unwrap().t - 5.0).abs() < 1e-9);
+        assert!(rect.hit(&down, 5.0, f64::INFINITY).is_none());
+        assert!(rect.hit(&down, 0.001, 5.0).is_none());
+    }
+}
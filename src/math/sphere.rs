@@ -1,5 +1,5 @@
-use super::Vec3;
-use crate::{HitRecord, Hittable, Material, Ray};
+use super::{Aabb, Vec3, PI, TAU};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Sampleable, Solid, StableHasher};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Sphere<M: Material> {
@@ -21,7 +21,22 @@ impl<M: Material> Sphere<M> {
         Box::new(Self::new(center, radius, material))
     }
 
-    fn get_hit_record(&self, r: &Ray, t: f64) -> Option<HitRecord> {
+    pub fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // Spherical coordinates of a point on the unit sphere, both mapped to [0, 1]
+    fn get_uv(p: &Vec3) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        (phi / TAU, theta / PI)
+    }
+
+    fn get_hit_record(&self, r: &Ray, t: f64) -> Option<HitRecord<'_>> {
         let point = r.at(t);
         let outward_normal = (point - self.center) / self.radius;
         let front_face = Vec3::dot(&r.direction, &outward_normal) < 0.0;
@@ -31,14 +46,22 @@ impl<M: Material> Sphere<M> {
             -outward_normal
         };
 
-        let hit_record: HitRecord = HitRecord::new(point, normal, t, front_face, &self.material);
+        let hit_record: HitRecord = HitRecord::new(
+            point,
+            normal,
+            t,
+            front_face,
+            Self::get_uv(&outward_normal),
+            &self.material,
+        );
 
-        return Some(hit_record);
+        Some(hit_record)
     }
 }
 
 impl<M: Material> Hittable for Sphere<M> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
         let oc: Vec3 = r.origin - self.center;
         let a = r.direction.length_squared();
         let half_b = Vec3::dot(&oc, &r.direction);
@@ -59,7 +82,60 @@ impl<M: Material> Hittable for Sphere<M> {
             }
         }
 
-        return None;
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Sphere", self.center, self.radius));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Solid for Sphere<M> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let oc: Vec3 = r.origin - self.center;
+        let a = r.direction.length_squared();
+        let half_b = Vec3::dot(&oc, &r.direction);
+        let c = oc.length_squared() - self.radius.powi(2);
+        let discriminant = half_b.powi(2) - a * c;
+
+        // Grazing rays do not go in
+        if discriminant <= 0.0 {
+            return vec![];
+        }
+        let root = discriminant.sqrt();
+        [(-half_b - root) / a, (-half_b + root) / a]
+            .iter()
+            .filter_map(|t| self.get_hit_record(r, *t))
+            .collect()
+    }
+}
+
+impl<M: Material> Sampleable for Sphere<M> {
+    // Seen from outside, a sphere covers a cone of directions: sample it uniformly
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.length_squared();
+        if distance_squared <= self.radius.powi(2) {
+            return None;
+        }
+
+        let cos_max = (1.0 - self.radius.powi(2) / distance_squared).sqrt();
+        let direction = Vec3::new_random_in_cone(&Vec3::unit(to_center), cos_max);
+        let pdf = 1.0 / (TAU * (1.0 - cos_max));
+
+        // Distance to the near side of the sphere along the sampled direction
+        let half_b = -Vec3::dot(&to_center, &direction);
+        let c = distance_squared - self.radius.powi(2);
+        let distance = -half_b - (half_b.powi(2) - c).max(0.0).sqrt();
+
+        Some((direction, distance, pdf))
     }
 }
 
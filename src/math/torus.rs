@@ -0,0 +1,245 @@
+use super::{first_hit, outward_hit, sort_hits, turn_fraction, Aabb, Vec3, PI, TAU};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Solid, StableHasher};
+
+// Ring of a tube of `minor_radius` around a circle of `major_radius` centered on `center` and
+// perpendicular to `axis`. The texture goes around the ring with u and around the tube with v
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Torus<M: Material> {
+    center: Vec3,
+    axis: Vec3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: M,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: M,
+    ) -> Self {
+        Torus {
+            center,
+            axis: Vec3::unit(axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    pub fn new_boxed(
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: M,
+    ) -> Box<Self> {
+        Box::new(Self::new(
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            material,
+        ))
+    }
+}
+
+impl<M: Material> Hittable for Torus<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_hit(self.intersections(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let ring = Aabb::around_disk(self.center, self.axis, self.major_radius);
+        let tube = Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        Some(Aabb::new(ring.min - tube, ring.max + tube))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&(
+            "Torus",
+            self.center,
+            self.axis,
+            self.major_radius,
+            self.minor_radius,
+        ));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Solid for Torus<M> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let (big, small) = (self.major_radius, self.minor_radius);
+        let length = r.direction.length();
+        let d = r.direction / length;
+
+        // Start from where the line enters the sphere around the torus, for the precision of the
+        // quartic, with t in units of length
+        let to_origin = r.origin - self.center;
+        let half_b = Vec3::dot(&to_origin, &d);
+        let c = to_origin.length_squared() - (big + small).powi(2);
+        if half_b.powi(2) - c < 0.0 {
+            return vec![];
+        }
+        let start = -half_b - (half_b.powi(2) - c).sqrt();
+        let o = to_origin + start * d;
+
+        // (|p|² + R² - r²)² = 4 R² (|p|² - (p.axis)²) with p = o + t d
+        let k = Vec3::dot(&o, &d);
+        let g = o.length_squared() + big.powi(2) - small.powi(2);
+        let (o_axial, d_axial) = (Vec3::dot(&o, &self.axis), Vec3::dot(&d, &self.axis));
+        let four_r2 = 4.0 * big.powi(2);
+        let coefficients = [
+            4.0 * k,
+            4.0 * k.powi(2) + 2.0 * g - four_r2 * (1.0 - d_axial.powi(2)),
+            4.0 * k * g - 2.0 * four_r2 * (k - o_axial * d_axial),
+            g.powi(2) - four_r2 * (o.length_squared() - o_axial.powi(2)),
+        ];
+
+        let (u_axis, v_axis) = Vec3::orthonormal_basis(&self.axis);
+        let mut hits: Vec<HitRecord> = solve_quartic(coefficients)
+            .into_iter()
+            .map(|t| {
+                let p = o + t * d;
+                // Closest point of the circle at the middle of the tube
+                let axial = Vec3::dot(&p, &self.axis);
+                let radial = p - axial * self.axis;
+                let ring = big * Vec3::unit(radial);
+                let outward = (p - ring) / small;
+                let around_tube = axial.atan2(radial.length() - big);
+                let uv = (
+                    turn_fraction(&radial, &u_axis, &v_axis),
+                    (around_tube + PI) / TAU,
+                );
+                outward_hit(r, (start + t) / length, outward, uv, &self.material)
+            })
+            .collect();
+        sort_hits(&mut hits);
+        hits
+    }
+}
+
+// Real roots of x⁴ + a x³ + b x² + c x + d with Ferrari's method, each polished with Newton's
+fn solve_quartic([a, b, c, d]: [f64; 4]) -> Vec<f64> {
+    // y⁴ + p y² + q y + r with x = y - a / 4
+    let shift = -a / 4.0;
+    let p = b - 3.0 * a * a / 8.0;
+    let q = c - a * b / 2.0 + a.powi(3) / 8.0;
+    let r = d - a * c / 4.0 + a * a * b / 16.0 - 3.0 * a.powi(4) / 256.0;
+
+    let mut roots = vec![];
+    let mut solve_quadratic = |b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            roots.push((-b - root) / 2.0);
+            roots.push((-b + root) / 2.0);
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // Quadratic in y²
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            for z in [
+                (-p - discriminant.sqrt()) / 2.0,
+                (-p + discriminant.sqrt()) / 2.0,
+            ]
+            .iter()
+            {
+                if *z >= 0.0 {
+                    solve_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // With m such that (y² + p / 2 + m)² = (s y - q / (2 s))², s = sqrt(2 m), from the
+        // resolvent cubic m³ + p m² + (p² / 4 - r) m - q² / 8
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            solve_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+            solve_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y + shift;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+                if df.abs() > 1e-12 {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+// Largest real root of x³ + a x² + b x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // z³ + p z + q with x = z - a / 3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a.powi(3) / 27.0 - a * b / 3.0 + c;
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+
+    let z = if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        (-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt()
+    } else if p < 0.0 {
+        // Three real roots, the first one is the largest
+        let angle = ((3.0 * q / (2.0 * p)) * (-3.0 / p).sqrt())
+            .clamp(-1.0, 1.0)
+            .acos()
+            / 3.0;
+        2.0 * (-p / 3.0).sqrt() * angle.cos()
+    } else {
+        (-q).cbrt()
+    };
+    z - a / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::check_hit, Color, Lambertian};
+
+    #[test]
+    fn torus_is_hit_on_its_tube() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let (x, y, z) = (
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let torus = Torus::new(origin, y, 2.0, 0.5, material);
+        assert!(torus
+            .hit(
+                &Ray::new(Vec3::new(0.0, 5.0, 0.0), -y),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        let onto_tube = Ray::new(Vec3::new(2.0, 5.0, 0.0), -y);
+        check_hit(&torus, &onto_tube, 4.5, y, true);
+        assert!(torus.hit(&onto_tube, 0.001, 4.0).is_none());
+        check_hit(
+            &torus,
+            &Ray::new(Vec3::new(-5.0, 0.0, 0.0), x),
+            2.5,
+            -x,
+            true,
+        );
+        // From the hole through both sides of the tube
+        let across = Ray::new(origin, z);
+        check_hit(&torus, &across, 1.5, -z, true);
+        assert_eq!(torus.intersections(&across).len(), 4);
+    }
+}
@@ -0,0 +1,201 @@
+use super::{rect::area_sample_to_direction, Aabb, Vec3};
+use crate::{stats, HitRecord, Hittable, Material, Ray, Sampleable, StableHasher};
+use rand::Rng;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Triangle<M: Material> {
+    vertices: [Vec3; 3],
+    normal: Vec3,
+    area: f64,
+    material: M,
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: M) -> Self {
+        let n = Vec3::cross(&(v1 - v0), &(v2 - v0));
+        Triangle {
+            vertices: [v0, v1, v2],
+            normal: Vec3::unit(n),
+            area: n.length() / 2.0,
+            material,
+        }
+    }
+
+    pub fn new_boxed(v0: Vec3, v1: Vec3, v2: Vec3, material: M) -> Box<Self> {
+        Box::new(Self::new(v0, v1, v2, material))
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+}
+
+impl<M: Material> Hittable for Triangle<M> {
+    // Möller–Trumbore intersection
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_intersection_test();
+        let [v0, v1, v2] = self.vertices;
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+
+        let p = Vec3::cross(&r.direction, &edge2);
+        let det = Vec3::dot(&edge1, &p);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = r.origin - v0;
+        let u = Vec3::dot(&s, &p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = Vec3::cross(&s, &edge1);
+        let v = Vec3::dot(&r.direction, &q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = Vec3::dot(&edge2, &q) * inv_det;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let front_face = Vec3::dot(&r.direction, &self.normal) < 0.0;
+        let normal = if front_face {
+            self.normal
+        } else {
+            -self.normal
+        };
+
+        Some(HitRecord::new(
+            r.at(t),
+            normal,
+            t,
+            front_face,
+            (u, v),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [v0, v1, v2] = self.vertices;
+        Some(Aabb::new(v0, v1).surrounding(&Aabb::new(v2, v2)))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Triangle", self.vertices));
+        self.material.fingerprint(hasher);
+    }
+}
+
+impl<M: Material> Sampleable for Triangle<M> {
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let mut rng = rand::thread_rng();
+        let (a, b): (f64, f64) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+        // Fold the unit square onto the triangle
+        let (a, b) = if a + b > 1.0 {
+            (1.0 - a, 1.0 - b)
+        } else {
+            (a, b)
+        };
+        let [v0, v1, v2] = self.vertices;
+        let point = v0 + a * (v1 - v0) + b * (v2 - v0);
+
+        area_sample_to_direction(origin, &point, &self.normal, self.area)
+    }
+}
+
+// A bunch of triangles sharing the same material
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mesh<M: Material> {
+    triangles: Vec<Triangle<M>>,
+    area: f64,
+}
+
+impl<M: Material + Clone> Mesh<M> {
+    pub fn new(vertices: &[Vec3], faces: &[[usize; 3]], material: M) -> Self {
+        let triangles: Vec<Triangle<M>> = faces
+            .iter()
+            .map(|&[a, b, c]| {
+                Triangle::new(vertices[a], vertices[b], vertices[c], material.clone())
+            })
+            .collect();
+        let area = triangles.iter().map(|t| t.area).sum();
+
+        Mesh { triangles, area }
+    }
+
+    pub fn new_boxed(vertices: &[Vec3], faces: &[[usize; 3]], material: M) -> Box<Self> {
+        Box::new(Self::new(vertices, faces, material))
+    }
+}
+
+impl<M: Material> Hittable for Mesh<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest_so_far = t_max;
+        let mut hit_anything: Option<HitRecord> = None;
+        for triangle in self.triangles.iter() {
+            if let Some(hit) = triangle.hit(r, t_min, closest_so_far) {
+                closest_so_far = hit.t;
+                hit_anything = Some(hit);
+            }
+        }
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.triangles
+            .iter()
+            .filter_map(|triangle| triangle.bounding_box())
+            .reduce(|a, b| a.surrounding(&b))
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Mesh", self.triangles.len()));
+        for triangle in self.triangles.iter() {
+            triangle.fingerprint(hasher);
+        }
+    }
+}
+
+impl<M: Material> Sampleable for Mesh<M> {
+    // Pick a triangle proportionally to its area, then a point on it
+    fn sample_direction(&self, origin: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let mut target = rand::thread_rng().gen_range(0.0..1.0) * self.area;
+        let triangle = self
+            .triangles
+            .iter()
+            .find(|t| {
+                target -= t.area;
+                target <= 0.0
+            })
+            .or_else(|| self.triangles.last())?;
+
+        // The pdf of the triangle alone is relative to its own area, rescale it to the whole mesh
+        let (direction, distance, pdf) = triangle.sample_direction(origin)?;
+        Some((direction, distance, pdf * triangle.area / self.area))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Lambertian};
+
+    #[test]
+    fn triangle_excludes_the_bounds_of_the_interval() {
+        let material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let triangle = Triangle::new(
+            Vec3::new(-1.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            material,
+        );
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!((triangle.hit(&down, 0.001, f64::INFINITY).unwrap().t - 5.0).abs() < 1e-9);
+        assert!(triangle.hit(&down, 5.0, f64::INFINITY).is_none());
+        assert!(triangle.hit(&down, 0.001, 5.0).is_none());
+    }
+}
@@ -52,6 +52,30 @@ impl Vec3 {
         }
     }
 
+    // Uniformly distributed direction in the cone around the unit vector `axis`
+    // whose half angle has `cos_max` for cosine
+    pub fn new_random_in_cone(axis: &Vec3, cos_max: f64) -> Self {
+        let mut rng = rand::thread_rng();
+        let cos_theta = 1.0 - rng.gen_range(0.0..1.0) * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        let phi: f64 = rng.gen_range(0.0..TAU);
+
+        let (u, v) = Vec3::orthonormal_basis(axis);
+        sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * axis
+    }
+
+    // Two unit vectors making with the unit vector `w` an orthonormal basis
+    pub fn orthonormal_basis(w: &Vec3) -> (Vec3, Vec3) {
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit(Vec3::cross(w, &a));
+        let u = Vec3::cross(w, &v);
+        (u, v)
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
@@ -72,6 +96,15 @@ impl Vec3 {
         )
     }
 
+    // Component wise minimum and maximum
+    pub fn min(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.x.min(v.x), u.y.min(v.y), u.z.min(v.z))
+    }
+
+    pub fn max(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.x.max(v.x), u.y.max(v.y), u.z.max(v.z))
+    }
+
     pub fn unit(vec1: Vec3) -> Vec3 {
         vec1 / vec1.length()
     }
@@ -82,9 +115,9 @@ impl Vec3 {
 
     pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
         let cos_theta = Vec3::dot(&-uv, n);
-        let r_out_perp = etai_over_etat * (uv + &(&cos_theta * n));
+        let r_out_perp = etai_over_etat * (uv + &(cos_theta * n));
         let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
-        return r_out_perp + r_out_parallel;
+        r_out_perp + r_out_parallel
     }
 }
 
@@ -160,7 +193,7 @@ impl ops::Mul<f64> for Vec3 {
     type Output = Vec3;
 
     fn mul(self, other: f64) -> Vec3 {
-        &self * &other
+        Vec3::new(self.x * other, self.y * other, self.z * other)
     }
 }
 
@@ -216,7 +249,7 @@ impl ops::Div<f64> for Vec3 {
     type Output = Vec3;
 
     fn div(self, other: f64) -> Vec3 {
-        &self / &other
+        Vec3::new(self.x / other, self.y / other, self.z / other)
     }
 }
 
@@ -232,7 +265,7 @@ impl ops::Div<Vec3> for f64 {
     type Output = Vec3;
 
     fn div(self, other: Vec3) -> Vec3 {
-        &self / &other
+        Vec3::new(self / other.x, self / other.y, self / other.z)
     }
 }
 
@@ -368,7 +401,7 @@ mod tests {
         let mut rng = rand::thread_rng();
 
         let vec1 = rand_vec3();
-        let result = &vec1 / &vec1.length();
+        let result = vec1 / vec1.length();
         assert_eq!(Vec3::unit(vec1), result);
 
         let n = rng.gen_range(0.0..1_000.0);
@@ -394,6 +427,16 @@ mod tests {
         assert!(rand_vec.length_squared() < 1.0);
     }
 
+    #[test]
+    fn random_in_cone() {
+        let axis = Vec3::unit(rand_vec3());
+        let cos_max = 0.8;
+        let rand_vec = Vec3::new_random_in_cone(&axis, cos_max);
+
+        assert!(rand_vec.length_squared() > 0.9999 && rand_vec.length_squared() < 1.0001);
+        assert!(Vec3::dot(&rand_vec, &axis) >= cos_max - 0.0001);
+    }
+
     #[test]
     fn random_unit() {
         let rand_vec = Vec3::new_random_unit();
@@ -0,0 +1,273 @@
+use ray_tracer::{CropWindow, RTError, Tile};
+use std::{env, time::Duration};
+
+// Command line options of the binary
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Options {
+    pub progressive: bool,
+    pub snapshot_every: Option<Duration>,
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: Option<Duration>,
+    pub resume: bool,
+    // Output then input checkpoints
+    pub merge: Option<(String, Vec<String>)>,
+    // Address to listen on for workers, or of the coordinator to work for
+    pub coordinator: Option<String>,
+    pub worker: Option<String>,
+    pub tile_size: Option<u32>,
+    // How long a worker may take to send back a tile before it is given to another one, and how
+    // long the coordinator waits without any worker
+    pub worker_timeout: Option<Duration>,
+    // Write the render statistics as JSON next to the image
+    pub stats: bool,
+    // Write the auxiliary buffers as images and with the image in an EXR file
+    pub aovs: bool,
+    // Denoise the render, or an EXR file written with `--aovs` without rendering anything
+    pub denoise: bool,
+    pub denoise_file: Option<String>,
+    // First and last frames of the animation to render, at `fps` frames per second
+    pub frames: Option<(u32, u32)>,
+    pub fps: Option<f64>,
+    pub skip_existing: bool,
+    // Assemble the frames into an animated GIF or PNG
+    pub gif: Option<String>,
+    pub apng: Option<String>,
+    pub dither: bool,
+    // Only render this part of the image, writing it alone or over the image of `fill_from`
+    pub crop: Option<CropWindow>,
+    pub fill_from: Option<String>,
+}
+
+impl Options {
+    pub fn from_args() -> Result<Self, RTError> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, RTError> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--progressive" => options.progressive = true,
+                "--snapshot-every" => {
+                    let seconds: f64 = parse_value(&arg, args.next())?;
+                    options.progressive = true;
+                    options.snapshot_every = Some(Duration::from_secs_f64(seconds));
+                }
+                "--checkpoint" => options.checkpoint = Some(parse_value(&arg, args.next())?),
+                "--checkpoint-every" => {
+                    let seconds: f64 = parse_value(&arg, args.next())?;
+                    options.checkpoint_every = Some(Duration::from_secs_f64(seconds));
+                }
+                "--resume" => options.resume = true,
+                "--stats" => options.stats = true,
+                "--aovs" => options.aovs = true,
+                "--denoise" => options.denoise = true,
+                "--denoise-file" => options.denoise_file = Some(parse_value(&arg, args.next())?),
+                // Takes all the remaining arguments
+                "--merge" => {
+                    let output: String = parse_value(&arg, args.next())?;
+                    let inputs: Vec<String> = args.by_ref().collect();
+                    if inputs.is_empty() {
+                        return Err(RTError::InvalidArgument(
+                            "--merge needs files to merge".to_string(),
+                        ));
+                    }
+                    options.merge = Some((output, inputs));
+                }
+                "--coordinator" => options.coordinator = Some(parse_value(&arg, args.next())?),
+                "--worker" => options.worker = Some(parse_value(&arg, args.next())?),
+                "--tile-size" => options.tile_size = Some(parse_value(&arg, args.next())?),
+                "--worker-timeout" => {
+                    let seconds = parse_positive(&arg, args.next())?;
+                    options.worker_timeout = Some(Duration::from_secs_f64(seconds));
+                }
+                "--frames" => {
+                    let first = parse_value(&arg, args.next())?;
+                    let last = parse_value(&arg, args.next())?;
+                    if last < first {
+                        return Err(RTError::InvalidArgument(format!(
+                            "--frames {} {}",
+                            first, last
+                        )));
+                    }
+                    options.frames = Some((first, last));
+                }
+                "--fps" => options.fps = Some(parse_positive(&arg, args.next())?),
+                "--skip-existing" => options.skip_existing = true,
+                "--gif" => options.gif = Some(parse_value(&arg, args.next())?),
+                "--apng" => options.apng = Some(parse_value(&arg, args.next())?),
+                "--dither" => options.dither = true,
+                "--crop" => {
+                    let mut value = || parse_value(&arg, args.next());
+                    let tile = Tile::new(value()?, value()?, value()?, value()?);
+                    options.crop = Some(CropWindow::Pixels(tile));
+                }
+                "--crop-normalized" => {
+                    let mut value = || parse_value(&arg, args.next());
+                    options.crop = Some(CropWindow::Normalized {
+                        x_min: value()?,
+                        y_min: value()?,
+                        x_max: value()?,
+                        y_max: value()?,
+                    });
+                }
+                "--fill-from" => options.fill_from = Some(parse_value(&arg, args.next())?),
+                _ => return Err(RTError::InvalidArgument(arg)),
+            }
+        }
+
+        if options.progressive && options.checkpoint.is_some() {
+            return Err(RTError::InvalidArgument(
+                "--checkpoint and --progressive are exclusive".to_string(),
+            ));
+        }
+        if options.resume && options.checkpoint.is_none() {
+            return Err(RTError::InvalidArgument(
+                "--resume needs a --checkpoint file".to_string(),
+            ));
+        }
+
+        if options.frames.is_none() {
+            let frame_options = [
+                ("--skip-existing", options.skip_existing),
+                ("--gif", options.gif.is_some()),
+                ("--apng", options.apng.is_some()),
+            ];
+            if let Some((arg, _)) = frame_options.iter().find(|(_, set)| *set) {
+                return Err(RTError::InvalidArgument(format!("{} needs --frames", arg)));
+            }
+        }
+
+        if options.fill_from.is_some() && options.crop.is_none() {
+            return Err(RTError::InvalidArgument(
+                "--fill-from needs a --crop window".to_string(),
+            ));
+        }
+        // The auxiliary buffers are collected along a plain render of the whole frame
+        if options.aovs || options.denoise {
+            let render_options = [
+                ("--crop", options.crop.is_some()),
+                ("--checkpoint", options.checkpoint.is_some()),
+                ("--progressive", options.progressive),
+                ("--coordinator", options.coordinator.is_some()),
+            ];
+            if let Some((arg, _)) = render_options.iter().find(|(_, set)| *set) {
+                return Err(RTError::InvalidArgument(format!(
+                    "--aovs and --denoise can not be used with {}",
+                    arg
+                )));
+            }
+        }
+
+        if options.coordinator.is_some() && options.worker.is_some() {
+            return Err(RTError::InvalidArgument(
+                "--coordinator and --worker are exclusive".to_string(),
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, RTError> {
+    let value = value.ok_or_else(|| RTError::InvalidArgument(format!("{} needs a value", arg)))?;
+    value
+        .parse()
+        .map_err(|_| RTError::InvalidArgument(format!("{} {}", arg, value)))
+}
+
+// A number of seconds, frames per second...: finite and above 0
+fn parse_positive(arg: &str, value: Option<String>) -> Result<f64, RTError> {
+    let number: f64 = parse_value(arg, value)?;
+    if number > 0.0 && number.is_finite() {
+        Ok(number)
+    } else {
+        Err(RTError::InvalidArgument(format!("{} {}", arg, number)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, RTError> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+
+        let options = parse(&["--snapshot-every", "2.5"]).unwrap();
+        assert!(options.progressive);
+        assert_eq!(options.snapshot_every, Some(Duration::from_millis(2500)));
+
+        let options = parse(&["--checkpoint", "render.ckpt", "--resume", "--stats"]).unwrap();
+        assert_eq!(options.checkpoint, Some("render.ckpt".to_string()));
+        assert!(options.resume);
+        assert!(options.stats);
+        assert!(parse(&["--aovs"]).unwrap().aovs);
+        let options = parse(&["--denoise-file", "img.exr"]).unwrap();
+        assert_eq!(options.denoise_file, Some("img.exr".to_string()));
+
+        let options = parse(&["--merge", "all.ckpt", "a.ckpt", "b.ckpt"]).unwrap();
+        assert_eq!(
+            options.merge,
+            Some((
+                "all.ckpt".to_string(),
+                vec!["a.ckpt".to_string(), "b.ckpt".to_string()]
+            ))
+        );
+
+        let options = parse(&["--coordinator", "0.0.0.0:7878", "--tile-size", "16"]).unwrap();
+        assert_eq!(options.coordinator, Some("0.0.0.0:7878".to_string()));
+        assert_eq!(options.tile_size, Some(16));
+        let options = parse(&["--coordinator", "0.0.0.0:7878", "--worker-timeout", "90"]).unwrap();
+        assert_eq!(options.worker_timeout, Some(Duration::from_secs(90)));
+        assert!(parse(&["--worker-timeout", "0"]).is_err());
+
+        let options = parse(&["--frames", "10", "20", "--fps", "30", "--skip-existing"]).unwrap();
+        assert_eq!(options.frames, Some((10, 20)));
+        assert_eq!(options.fps, Some(30.0));
+        assert!(parse(&["--frames", "0", "1", "--fps", "0"]).is_err());
+        assert!(parse(&["--frames", "0", "1", "--fps", "NaN"]).is_err());
+        assert!(options.skip_existing);
+        assert!(parse(&["--frames", "20", "10"]).is_err());
+        assert!(parse(&["--skip-existing"]).is_err());
+        let options = parse(&["--frames", "0", "9", "--gif", "a.gif", "--dither"]).unwrap();
+        assert_eq!(options.gif, Some("a.gif".to_string()));
+        assert!(options.dither);
+        assert!(parse(&["--apng", "a.png"]).is_err());
+
+        let options = parse(&["--crop", "10", "20", "30", "40", "--fill-from", "a.jpg"]).unwrap();
+        assert_eq!(
+            options.crop,
+            Some(CropWindow::Pixels(Tile::new(10, 20, 30, 40)))
+        );
+        assert_eq!(options.fill_from, Some("a.jpg".to_string()));
+        let options = parse(&["--crop-normalized", "0.25", "0", "0.75", "0.5"]).unwrap();
+        assert_eq!(
+            options.crop,
+            Some(CropWindow::Normalized {
+                x_min: 0.25,
+                y_min: 0.0,
+                x_max: 0.75,
+                y_max: 0.5,
+            })
+        );
+        assert!(parse(&["--crop", "1", "2", "3"]).is_err());
+        assert!(parse(&["--fill-from", "a.jpg"]).is_err());
+        assert!(parse(&["--crop", "0", "0", "8", "8", "--denoise"]).is_err());
+        assert!(parse(&["--aovs", "--progressive"]).is_err());
+        assert!(parse(&["--denoise", "--coordinator", "a:1"]).is_err());
+
+        assert!(parse(&["--coordinator", "a:1", "--worker", "a:1"]).is_err());
+        assert!(parse(&["--tile-size", "big"]).is_err());
+        assert!(parse(&["--merge", "all.ckpt"]).is_err());
+        assert!(parse(&["--resume"]).is_err());
+        assert!(parse(&["--checkpoint", "a.ckpt", "--snapshot-every", "5"]).is_err());
+        assert!(parse(&["--snapshot-every"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{RenderStats, WorkerEvent};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// Where a render stands. With adaptive sampling `total_rays` is only an upper bound
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Progress {
+    pub rays_traced: u64,
+    pub total_rays: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        if self.total_rays == 0 {
+            1.0
+        } else {
+            (self.rays_traced as f64 / self.total_rays as f64).min(1.0)
+        }
+    }
+
+    // Estimated time left, assuming the remaining rays take as long as the ones already traced
+    pub fn eta(&self) -> Option<Duration> {
+        if self.rays_traced == 0 {
+            return None;
+        }
+        let remaining = self.total_rays.saturating_sub(self.rays_traced);
+        Some(
+            self.elapsed
+                .mul_f64(remaining as f64 / self.rays_traced as f64),
+        )
+    }
+}
+
+pub trait ProgressObserver {
+    fn progress(&mut self, progress: &Progress);
+
+    // Only called by distributed renders
+    fn worker(&mut self, _event: &WorkerEvent) {}
+}
+
+impl<T: FnMut(&Progress)> ProgressObserver for T {
+    fn progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+// Prints the progress on the standard output
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PrintProgress;
+
+impl ProgressObserver for PrintProgress {
+    fn progress(&mut self, progress: &Progress) {
+        match progress.eta() {
+            Some(eta) => println!(
+                "{:.2}% done, {} over {}, {:.0} s left",
+                progress.fraction() * 100.0,
+                progress.rays_traced,
+                progress.total_rays,
+                eta.as_secs_f64()
+            ),
+            None => println!(
+                "{:.2}% done, {} over {}",
+                progress.fraction() * 100.0,
+                progress.rays_traced,
+                progress.total_rays
+            ),
+        }
+    }
+
+    fn worker(&mut self, event: &WorkerEvent) {
+        match event {
+            WorkerEvent::Connected(address) => println!("Worker {} connected", address),
+            WorkerEvent::Lost(address, reason) => println!("Worker {} lost: {}", address, reason),
+        }
+    }
+}
+
+// Shared flag to stop a render from another thread. Clones all refer to the same flag
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Cancellation::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// How a render reports its progress, every `report_every` and once at the end, and how it can be
+// stopped. A cancelled render returns the image as it is, with the samples traced so far: it can
+// be written as it is, the pixels not rendered being black. The statistics of the renders made with
+// it add up in `stats`. By default nothing is reported, see `PrintProgress`
+pub struct RenderControl<'a> {
+    pub observer: Box<dyn ProgressObserver + 'a>,
+    pub report_every: Duration,
+    pub cancellation: Cancellation,
+    pub stats: RenderStats,
+}
+
+impl<'a> RenderControl<'a> {
+    pub fn new(observer: impl ProgressObserver + 'a, cancellation: Cancellation) -> Self {
+        RenderControl {
+            observer: Box::new(observer),
+            report_every: Duration::from_secs(1),
+            cancellation,
+            stats: RenderStats::default(),
+        }
+    }
+
+    // Reports nothing and can not be cancelled
+    pub fn silent() -> Self {
+        RenderControl::new(|_: &Progress| {}, Cancellation::new())
+    }
+}
+
+impl Default for RenderControl<'_> {
+    fn default() -> Self {
+        RenderControl::silent()
+    }
+}
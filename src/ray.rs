@@ -1,7 +1,8 @@
 use crate::{
-    math::{self, Vec3},
-    Color, Material, World,
+    math::{self, Aabb, Vec3},
+    stats, Color, Material, StableHasher, World,
 };
+use rand::Rng;
 // use std::fmt::Debug;
 
 pub struct Ray {
@@ -14,38 +15,115 @@ impl Ray {
         Self { origin, direction }
     }
 
-    pub fn at(self: &Self, t: f64) -> Vec3 {
+    pub fn at(&self, t: f64) -> Vec3 {
         self.origin + t * self.direction
     }
 
+    // Color brought back by the ray, with a hard limit of `depth` bounces
     pub fn ray_color<F>(&self, world: &World<F>, depth: u32) -> Color
     where
         F: Fn(&Ray) -> Color,
     {
-        match (world.hit(&self, 0.001, math::INFINITY), depth) {
-            // If the ray bounced enougth (depth = 0) we consider it is now completly black and we stop here
-            (_, 0) => Color::new(0.0, 0.0, 0.0),
-
-            // If the ray hit something ,we scater it and decrement the depth counter
-            (Some(hit_record), depth) => {
-                if let Some((scattered, attenuation)) =
-                    hit_record.material.scatter(&self, &hit_record)
-                {
-                    let emitted = hit_record.material.emitted();
-                    emitted + attenuation * scattered.ray_color(world, depth - 1)
-                } else {
-                    hit_record.material.emitted()
+        self.path_color(world, Some(depth), None)
+    }
+
+    // Follow the path of the ray from bounce to bounce, `throughput` being the fraction of the light
+    // found further along the path that makes it back to the camera. After `russian_roulette`
+    // bounces, the path goes on with a probability given by its throughput, the survivors being
+    // weighted up to keep the result unbiased. `max_depth` caps the number of bounces anyway
+    pub fn path_color<F>(
+        &self,
+        world: &World<F>,
+        max_depth: Option<u32>,
+        russian_roulette: Option<u32>,
+    ) -> Color
+    where
+        F: Fn(&Ray) -> Color,
+    {
+        let mut rng = rand::thread_rng();
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(self.origin, self.direction);
+        let mut bounces = 0;
+        // Tells if hitting one of the sampled lights should count: after a non specular bounce its
+        // light has already been gathered by `World::direct_lighting`
+        let mut count_lights = true;
+
+        stats::count(|stats| stats.primary_rays += 1);
+        if max_depth == Some(0) {
+            stats::count(|stats| stats.paths_depth_limit += 1);
+            return color;
+        }
+
+        loop {
+            let (hit_record, is_light) = match world.hit_with_lights(&ray, 0.001, math::INFINITY) {
+                Some(hit) => hit,
+                // If the ray hit nothing we draw the background
+                None => {
+                    stats::count(|stats| stats.paths_escaped += 1);
+                    return color + throughput * (world.background)(&ray);
                 }
+            };
+
+            // Composite materials pick one of their layers, the chosen one is then used for both emission and scattering
+            let material = hit_record.material.resolve(&ray, &hit_record);
+            if !is_light || count_lights {
+                color = color + throughput * material.emitted(&ray, &hit_record);
             }
+            color = color + throughput * world.direct_lighting(&ray, &hit_record, material);
 
-            // If the ray hit nothing we draw the background
-            (None, _) => (&world.background)(&self),
+            let (scattered, attenuation) = match material.scatter(&ray, &hit_record) {
+                Some(scatter) => scatter,
+                None => {
+                    stats::count(|stats| stats.paths_absorbed += 1);
+                    return color;
+                }
+            };
+            throughput = throughput * attenuation;
+            bounces += 1;
+
+            if max_depth.is_some_and(|max_depth| bounces >= max_depth) {
+                stats::count(|stats| stats.paths_depth_limit += 1);
+                return color;
+            }
+            if russian_roulette.is_some_and(|after| bounces >= after) {
+                let survival = throughput
+                    .r()
+                    .max(throughput.g())
+                    .max(throughput.b())
+                    .min(0.95);
+                if rng.gen_range(0.0..1.0) >= survival {
+                    stats::count(|stats| stats.paths_russian_roulette += 1);
+                    return color;
+                }
+                throughput = Color::new_with_vec(throughput.vec / survival);
+            }
+
+            stats::count(|stats| stats.secondary_rays += 1);
+            count_lights = material.is_specular();
+            ray = scattered;
         }
     }
 }
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    // Box around the object, None when it is unbounded
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    // Hash everything the object is made of, its material included, so that a checkpoint is not
+    // resumed with another scene
+    fn fingerprint(&self, hasher: &mut StableHasher);
+}
+
+// Closed object with an inside, which can be carved. Its intersections are all the crossings of its
+// surface by the whole line of the ray, negative `t` included, sorted along it: `front_face` tells
+// whether the ray goes in or out there
+pub trait Solid: Hittable {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>>;
 }
 
 // #[derive(Debug, PartialEq, Clone, Copy)]
@@ -54,6 +132,8 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
+    pub u: f64,
+    pub v: f64,
     pub material: &'a dyn Material,
 }
 
@@ -63,6 +143,7 @@ impl<'a> HitRecord<'a> {
         normal: Vec3,
         t: f64,
         front_face: bool,
+        (u, v): (f64, f64),
         material: &'a dyn Material,
     ) -> Self {
         HitRecord {
@@ -70,6 +151,8 @@ impl<'a> HitRecord<'a> {
             normal,
             t,
             front_face,
+            u,
+            v,
             material,
         }
     }
@@ -136,3 +219,52 @@ impl<'a> HitRecord<'a> {
 //         assert_eq!(result.vec.z, 0.5);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Sphere, HitRecord};
+
+    // Glows with a radiance of 1 and sends back half of the light it gets
+    #[derive(Debug, Clone, Copy)]
+    struct GlowingMirror;
+
+    impl Material for GlowingMirror {
+        fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+            Some((
+                Ray::new(hit_record.point, hit_record.normal),
+                Color::new(0.5, 0.5, 0.5),
+            ))
+        }
+
+        fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+            Color::new(1.0, 1.0, 1.0)
+        }
+
+        fn fingerprint(&self, hasher: &mut StableHasher) {
+            hasher.write_debug(self);
+        }
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        // Inside the sphere every bounce adds half of the previous one: 1 + 1/2 + 1/4 + ... = 2
+        let mut world = World::new(|_ray: &Ray| Color::new(0.0, 0.0, 0.0));
+        world.add(Sphere::new_boxed(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            GlowingMirror,
+        ));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let capped = ray.ray_color(&world, 3);
+        assert!((capped.r() - 1.75).abs() < 1e-9);
+
+        let samples = 20_000;
+        let mean = (0..samples)
+            .map(|_| ray.path_color(&world, None, Some(1)).r())
+            .sum::<f64>()
+            / samples as f64;
+        assert!((mean - 2.0).abs() < 0.03, "mean {}", mean);
+    }
+}
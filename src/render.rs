@@ -0,0 +1,997 @@
+use crate::{
+    scene_hash, settings_hash, stats, AovSamples, Aovs, Camera, Checkpoint, Checkpointing, Color,
+    Filter, Image, PixelStats, Progress, RTError, Ray, RenderControl, StereoCamera, StereoLayout,
+    World,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// Stop sampling a pixel once the relative error of its mean drops under `threshold`,
+// taking at least `min_samples` and at most `max_samples` samples
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f64) -> Self {
+        AdaptiveSampling {
+            min_samples,
+            max_samples,
+            threshold,
+        }
+    }
+}
+
+// Part of the image to render, the rays being the ones of the whole frame. In pixels, or in
+// fractions of the size of the image from 0 to 1, both from the bottom left corner
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CropWindow {
+    Pixels(Tile),
+    Normalized {
+        x_min: f64,
+        y_min: f64,
+        x_max: f64,
+        y_max: f64,
+    },
+}
+
+impl CropWindow {
+    // Pixels of the window, within an image of `width` x `height` pixels
+    pub fn tile(&self, width: u32, height: u32) -> Tile {
+        let (x_min, y_min, x_max, y_max) = match *self {
+            CropWindow::Pixels(tile) => (tile.x, tile.y, tile.x + tile.width, tile.y + tile.height),
+            CropWindow::Normalized {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (
+                (x_min * width as f64).floor().max(0.0) as u32,
+                (y_min * height as f64).floor().max(0.0) as u32,
+                (x_max * width as f64).ceil().max(0.0) as u32,
+                (y_max * height as f64).ceil().max(0.0) as u32,
+            ),
+        };
+        let (x_min, y_min) = (x_min.min(width), y_min.min(height));
+        let (x_max, y_max) = (x_max.clamp(x_min, width), y_max.clamp(y_min, height));
+        Tile::new(x_min, y_min, x_max - x_min, y_max - y_min)
+    }
+}
+
+// What is kept of a render with a crop window: the pixels of the window only, or the whole frame
+// with the pixels outside of the window taken from a previous render of the same size
+#[derive(Debug, PartialEq, Clone)]
+pub enum CropOutput {
+    Window,
+    FillFrom(Image),
+}
+
+// With `adaptive` set, `samples_per_pixel` is ignored and its bounds are used instead.
+// Paths are cut short by Russian roulette after `russian_roulette` bounces, `depth` being a hard limit.
+// With `crop` set only the pixels of the window are rendered, see `apply_crop` for the result
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RenderSettings {
+    pub samples_per_pixel: u32,
+    pub depth: Option<u32>,
+    pub russian_roulette: Option<u32>,
+    pub filter: Filter,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub crop: Option<CropWindow>,
+}
+
+impl RenderSettings {
+    pub fn new(samples_per_pixel: u32, depth: u32) -> Self {
+        RenderSettings {
+            samples_per_pixel,
+            depth: Some(depth),
+            russian_roulette: Some(3),
+            filter: Filter::default(),
+            adaptive: None,
+            crop: None,
+        }
+    }
+
+    // Pixels to render in `img`
+    pub(crate) fn area(&self, img: &Image) -> Tile {
+        match self.crop {
+            Some(crop) => crop.tile(img.width, img.height),
+            None => Tile::full_frame(img),
+        }
+    }
+
+    pub fn max_samples_per_pixel(&self) -> u32 {
+        match self.adaptive {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.samples_per_pixel,
+        }
+    }
+
+    fn is_pixel_done(&self, stats: &PixelStats) -> bool {
+        match self.adaptive {
+            Some(adaptive) => {
+                stats.count >= adaptive.max_samples
+                    || (stats.count >= adaptive.min_samples
+                        && stats.relative_error() < adaptive.threshold)
+            }
+            None => stats.count >= self.samples_per_pixel,
+        }
+    }
+}
+
+pub fn create_img<F>(
+    img: Image,
+    world: World<F>,
+    samples_per_pixel: u32,
+    camera: Camera,
+    depth: u32,
+) -> Image
+where
+    F: Fn(&Ray) -> Color,
+{
+    render(
+        img,
+        &world,
+        &camera,
+        &RenderSettings::new(samples_per_pixel, depth),
+    )
+}
+
+// Same as `create_img` for the pixels of `window` only, made into `output` by `apply_crop`
+pub fn create_img_with_crop<F>(
+    img: Image,
+    world: World<F>,
+    samples_per_pixel: u32,
+    camera: Camera,
+    depth: u32,
+    window: CropWindow,
+    output: CropOutput,
+) -> Result<Image, RTError>
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut settings = RenderSettings::new(samples_per_pixel, depth);
+    settings.crop = Some(window);
+    let img = render(img, &world, &camera, &settings);
+    apply_crop(&img, &window, &output)
+}
+
+// Turn a full frame render made with the crop window `window` into the requested output
+pub fn apply_crop(img: &Image, window: &CropWindow, output: &CropOutput) -> Result<Image, RTError> {
+    let tile = window.tile(img.width, img.height);
+    let cropped = img.crop(tile.x, tile.y, tile.width, tile.height);
+    match output {
+        CropOutput::Window => Ok(cropped),
+        CropOutput::FillFrom(previous) => {
+            if (previous.width, previous.height) != (img.width, img.height) {
+                return Err(RTError::InvalidArgument(format!(
+                    "the image to fill the frame from is {}x{} instead of {}x{}",
+                    previous.width, previous.height, img.width, img.height
+                )));
+            }
+            let mut filled = previous.clone();
+            filled.paste(&cropped, tile.x, tile.y);
+            Ok(filled)
+        }
+    }
+}
+
+// Render without reporting anything, see `render_with_control` to observe or cancel the render
+pub fn render<F>(img: Image, world: &World<F>, camera: &Camera, settings: &RenderSettings) -> Image
+where
+    F: Fn(&Ray) -> Color,
+{
+    render_with_control(img, world, camera, settings, &mut RenderControl::default())
+}
+
+pub fn render_with_control<F>(
+    img: Image,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    control: &mut RenderControl,
+) -> Image
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut rng = StdRng::from_entropy();
+    let tile = settings.area(&img);
+    match render_rows(
+        img,
+        &tile,
+        world,
+        camera,
+        settings,
+        &mut rng,
+        control,
+        None,
+        &mut |_, _| Ok(()),
+    ) {
+        Ok(img) => img,
+        Err(_) => unreachable!("rendering only fails when the row callback does"),
+    }
+}
+
+// Render along with the auxiliary buffers, made of the first hits of the same rays as the image and
+// filtered the same way, so that they line up with it
+pub fn render_with_aovs<F>(
+    img: Image,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    control: &mut RenderControl,
+) -> (Image, Aovs)
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut rng = StdRng::from_entropy();
+    let tile = settings.area(&img);
+    let mut aovs = AovSamples::new(img.width, img.height);
+    match render_rows(
+        img,
+        &tile,
+        world,
+        camera,
+        settings,
+        &mut rng,
+        control,
+        Some(&mut aovs),
+        &mut |_, _| Ok(()),
+    ) {
+        Ok(img) => (img, aovs.finish()),
+        Err(_) => unreachable!("rendering only fails when the row callback does"),
+    }
+}
+
+// Render each eye of `stereo` in its half of `img`, whose width (side by side) or height (over
+// under) should be even. The cameras of the eyes should have the aspect ratio of these halves
+pub fn render_stereo<F>(
+    mut img: Image,
+    world: &World<F>,
+    stereo: &StereoCamera,
+    settings: &RenderSettings,
+    control: &mut RenderControl,
+) -> Image
+where
+    F: Fn(&Ray) -> Color,
+{
+    let (width, height) = match stereo.layout {
+        StereoLayout::SideBySide => (img.width / 2, img.height),
+        StereoLayout::OverUnder => (img.width, img.height / 2),
+    };
+    let left = render_with_control(
+        Image::new(width, height),
+        world,
+        &stereo.left,
+        settings,
+        control,
+    );
+    if control.cancellation.is_cancelled() {
+        img.paste(&left, 0, img.height - height);
+        return img;
+    }
+    let right = render_with_control(
+        Image::new(width, height),
+        world,
+        &stereo.right,
+        settings,
+        control,
+    );
+
+    // Rows go up from the bottom of the image, the top half is the one further from 0
+    match stereo.layout {
+        StereoLayout::SideBySide => {
+            img.paste(&left, 0, 0);
+            img.paste(&right, width, 0);
+        }
+        StereoLayout::OverUnder => {
+            img.paste(&left, 0, height);
+            img.paste(&right, 0, 0);
+        }
+    }
+    img
+}
+
+// Render the whole frame in passes: each one brings every pixel up to twice as many samples as the
+// previous one, until the settings are met. `snapshot` gets the image after every pass, or during
+// the passes if `snapshot_every` is set and that much time went by since the last snapshot
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Progressive {
+    pub first_pass_samples: u32,
+    pub snapshot_every: Option<Duration>,
+}
+
+impl Progressive {
+    pub fn new(first_pass_samples: u32, snapshot_every: Option<Duration>) -> Self {
+        Progressive {
+            first_pass_samples,
+            snapshot_every,
+        }
+    }
+}
+
+pub fn render_progressive<F>(
+    mut img: Image,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    progressive: &Progressive,
+    control: &mut RenderControl,
+    snapshot: &mut dyn FnMut(&Image) -> Result<(), RTError>,
+) -> Result<Image, RTError>
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut rng = StdRng::from_entropy();
+    let max_samples = settings.max_samples_per_pixel();
+    let mut pass_samples = progressive.first_pass_samples.clamp(1, max_samples.max(1));
+    let mut last_snapshot = Instant::now();
+
+    loop {
+        let mut pass_settings = *settings;
+        match pass_settings.adaptive.as_mut() {
+            Some(adaptive) => adaptive.max_samples = pass_samples,
+            None => pass_settings.samples_per_pixel = pass_samples,
+        }
+
+        let tile = pass_settings.area(&img);
+        img = render_rows(
+            img,
+            &tile,
+            world,
+            camera,
+            &pass_settings,
+            &mut rng,
+            control,
+            None,
+            &mut |img, _| match progressive.snapshot_every {
+                Some(every) if last_snapshot.elapsed() >= every => {
+                    last_snapshot = Instant::now();
+                    snapshot(img)
+                }
+                _ => Ok(()),
+            },
+        )?;
+
+        if control.cancellation.is_cancelled() {
+            return Ok(img);
+        }
+        if progressive.snapshot_every.is_none() || pass_samples >= max_samples {
+            last_snapshot = Instant::now();
+            snapshot(&img)?;
+        }
+
+        if pass_samples >= max_samples {
+            return Ok(img);
+        }
+        pass_samples = pass_samples.saturating_mul(2).min(max_samples);
+    }
+}
+
+// Render while saving the progress to a checkpoint from time to time. With `resume`, the render
+// carries on from the checkpoint instead of starting over from `img`, as long as the checkpoint
+// was made with the same scene, camera, image size and settings. A cancelled render is saved too
+pub fn render_with_checkpoints<F>(
+    img: Image,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    checkpointing: &Checkpointing,
+    resume: bool,
+    control: &mut RenderControl,
+) -> Result<Image, RTError>
+where
+    F: Fn(&Ray) -> Color,
+{
+    let scene_hash = scene_hash(world, camera);
+    let hash = settings_hash(&img, scene_hash, settings);
+    let (img, mut rng) = if resume {
+        let checkpoint = Checkpoint::load(&checkpointing.path)?;
+        if checkpoint.settings_hash != hash {
+            return Err(RTError::CheckpointMismatch {
+                expected: hash,
+                found: checkpoint.settings_hash,
+            });
+        }
+        (checkpoint.img, StdRng::seed_from_u64(checkpoint.rng_state))
+    } else {
+        (img, StdRng::from_entropy())
+    };
+
+    // The generator is reseeded with the saved state, so a resumed render puts its samples at the
+    // same positions in the pixels as if it had never stopped. Materials and lenses draw from their
+    // own thread generator, so the paths themselves differ: the result is as good, not identical
+    let save = |img: &Image, rng: &mut StdRng| {
+        let rng_state = rng.gen();
+        *rng = StdRng::seed_from_u64(rng_state);
+        Checkpoint {
+            scene_hash,
+            settings_hash: hash,
+            rng_state,
+            img: img.clone(),
+        }
+        .save(&checkpointing.path)
+    };
+
+    let mut last_checkpoint = Instant::now();
+    let tile = settings.area(&img);
+    let img = render_rows(
+        img,
+        &tile,
+        world,
+        camera,
+        settings,
+        &mut rng,
+        control,
+        None,
+        &mut |img, rng| {
+            if last_checkpoint.elapsed() >= checkpointing.every {
+                last_checkpoint = Instant::now();
+                save(img, rng)
+            } else {
+                Ok(())
+            }
+        },
+    )?;
+    save(&img, &mut rng)?;
+
+    Ok(img)
+}
+
+// A rectangle of pixels of the image, `x` and `y` being its bottom left pixel
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Tile {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full_frame(img: &Image) -> Self {
+        Tile::new(0, 0, img.width, img.height)
+    }
+
+    // Cut the image in tiles of `size` x `size` pixels, the last ones of each row and column
+    // being smaller when the size of the image is not a multiple of it
+    pub fn split(width: u32, height: u32, size: u32) -> Vec<Tile> {
+        let size = size.max(1);
+        let mut tiles = vec![];
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                tiles.push(Tile::new(x, y, size.min(width - x), size.min(height - y)));
+            }
+        }
+        tiles
+    }
+
+    pub fn contains(&self, w: u32, h: u32) -> bool {
+        w >= self.x && w < self.x + self.width && h >= self.y && h < self.y + self.height
+    }
+}
+
+// Render only the pixels of `tile`. The result has the size of the whole image but only holds the
+// pixels of the tile, plus the ones next to it where the filter spread its samples. Adding the
+// renders of all the tiles with `Image::merge` gives the whole image
+pub fn render_tile<F>(
+    width: u32,
+    height: u32,
+    tile: &Tile,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Image
+where
+    F: Fn(&Ray) -> Color,
+{
+    let mut rng = StdRng::from_entropy();
+    let img = Image::new(width, height);
+    match render_rows(
+        img,
+        tile,
+        world,
+        camera,
+        settings,
+        &mut rng,
+        &mut RenderControl::silent(),
+        None,
+        &mut |_, _| Ok(()),
+    ) {
+        Ok(img) => img,
+        Err(_) => unreachable!("rendering only fails when the row callback does"),
+    }
+}
+
+// Trace the missing samples of every pixel of `tile`, `on_row` is called after each row of pixels.
+// The first hits of the samples go to `aovs` if set
+#[allow(clippy::too_many_arguments)]
+fn render_rows<F>(
+    mut img: Image,
+    tile: &Tile,
+    world: &World<F>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    rng: &mut StdRng,
+    control: &mut RenderControl,
+    mut aovs: Option<&mut AovSamples>,
+    on_row: &mut dyn FnMut(&Image, &mut StdRng) -> Result<(), RTError>,
+) -> Result<Image, RTError>
+where
+    F: Fn(&Ray) -> Color,
+{
+    // With adaptive sampling this is only an upper bound
+    let already_traced: u64 = img
+        .stats
+        .iter()
+        .filter(|((w, h), _)| tile.contains(*w, *h))
+        .map(|(_, s)| s.count as u64)
+        .sum();
+    let total_rays: u64 =
+        (tile.height as u64 * tile.width as u64 * settings.max_samples_per_pixel() as u64)
+            .saturating_sub(already_traced);
+    let mut rays_traced: u64 = 0;
+
+    let start = Instant::now();
+    let mut last_report = start;
+    stats::take_thread_stats();
+
+    'rows: for h in (tile.y..tile.y + tile.height).rev() {
+        for w in tile.x..tile.x + tile.width {
+            while !settings.is_pixel_done(&img.get_pixel_stats(w, h)) {
+                if control.cancellation.is_cancelled() {
+                    break 'rows;
+                }
+
+                // Position of the sample in pixel units, pixel (w, h) covering [w, w + 1[ x [h, h + 1[
+                let x = w as f64 + rng.gen_range(0.0..1.0);
+                let y = h as f64 + rng.gen_range(0.0..1.0);
+                let (s, t) = (x / img.width as f64, y / img.height as f64);
+
+                // A ray stopped by the barrel of the lens brings no light
+                let path_color = |ray: &Option<Ray>| match ray {
+                    Some(ray) => ray.path_color(world, settings.depth, settings.russian_roulette),
+                    None => Color::new(0.0, 0.0, 0.0),
+                };
+                let (ray, ray_color) = if camera.lens.chromatic_aberration == 0.0 {
+                    let ray = camera.get_ray(s, t);
+                    let ray_color = path_color(&ray);
+                    (ray, ray_color)
+                } else {
+                    // Each channel sees a slightly different image, so it gets its own ray. The
+                    // green one stands for the sample in the auxiliary buffers
+                    let ray = camera.get_channel_ray(s, t, 1);
+                    let (red, blue) = (
+                        path_color(&camera.get_channel_ray(s, t, 0)),
+                        path_color(&camera.get_channel_ray(s, t, 2)),
+                    );
+                    let green = path_color(&ray);
+                    (ray, Color::new(red.r(), green.g(), blue.b()))
+                };
+                let ray_color = Color::new_with_vec(camera.film_scale() * ray_color.vec);
+                img.add_sample_stats(w, h, &ray_color);
+                splat_sample(&mut img, &settings.filter, x, y, ray_color);
+                if let Some(aovs) = aovs.as_mut() {
+                    // What a stopped ray would have hit is seen through the center of the lens
+                    let ray = ray.unwrap_or_else(|| camera.get_pinhole_ray(s, t));
+                    let hit = aovs.first_hit(world, camera, &ray);
+                    for_each_in_reach(&img, &settings.filter, x, y, |w, h, weight| {
+                        aovs.add(w, h, weight, &hit)
+                    });
+                }
+
+                rays_traced += 1;
+                if last_report.elapsed() >= control.report_every {
+                    control.observer.progress(&Progress {
+                        rays_traced,
+                        total_rays,
+                        elapsed: start.elapsed(),
+                    });
+                    last_report = Instant::now();
+                }
+            }
+        }
+        on_row(&img, rng)?;
+    }
+
+    let mut stats = stats::take_thread_stats();
+    stats.threads = 1;
+    stats.elapsed = start.elapsed();
+    control.stats.merge(&stats);
+    control.observer.progress(&Progress {
+        rays_traced,
+        total_rays,
+        elapsed: start.elapsed(),
+    });
+
+    Ok(img)
+}
+
+// Spread a sample over the pixels in reach of the filter. Samples never fall outside of the image
+// and each pixel is normalized by its own sum of weights, so the edges do not get darker
+fn splat_sample(img: &mut Image, filter: &Filter, x: f64, y: f64, color: Color) {
+    let mut weights = vec![];
+    for_each_in_reach(img, filter, x, y, |w, h, weight| {
+        weights.push((w, h, weight))
+    });
+    for (w, h, weight) in weights {
+        img.splat(w, h, color, weight);
+    }
+}
+
+// Call `f` with the pixels of `img` in reach of the filter for a sample at (x, y) and their weights
+fn for_each_in_reach(
+    img: &Image,
+    filter: &Filter,
+    x: f64,
+    y: f64,
+    mut f: impl FnMut(u32, u32, f64),
+) {
+    let radius = filter.radius();
+    let w_min = (x - 0.5 - radius).floor().max(0.0) as u32;
+    let w_max = ((x - 0.5 + radius).ceil() as u32).min(img.width - 1);
+    let h_min = (y - 0.5 - radius).floor().max(0.0) as u32;
+    let h_max = ((y - 0.5 + radius).ceil() as u32).min(img.height - 1);
+
+    for h in h_min..=h_max {
+        for w in w_min..=w_max {
+            let weight = filter.weight(x - (w as f64 + 0.5), y - (h as f64 + 0.5));
+            if weight != 0.0 {
+                f(w, h, weight);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{Vec3, PI},
+        Cancellation, Convergence,
+    };
+
+    #[test]
+    fn adaptive_sampling_stops_on_flat_pixels() {
+        // Nothing in the world: every pixel is the plain background
+        let world = World::new(|_ray: &Ray| Color::new(0.5, 0.5, 0.5));
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut settings = RenderSettings::new(1, 10);
+        settings.adaptive = Some(AdaptiveSampling::new(4, 64, 0.01));
+
+        let img = render(Image::new(4, 4), &world, &camera, &settings);
+
+        for h in 0..img.height {
+            for w in 0..img.width {
+                assert_eq!(img.get_pixel_stats(w, h).count, 4);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_stay_in_the_frame() {
+        for (width, height) in [(1, 4), (4, 4)].iter() {
+            let aspect_ratio = *width as f64 / *height as f64;
+            // White in the field of view, red outside of it or for broken rays
+            let world = World::new(move |ray: &Ray| {
+                let d = ray.direction;
+                if d.x.abs() <= -d.z * aspect_ratio + 1e-9 && d.y.abs() <= -d.z + 1e-9 {
+                    Color::new(1.0, 1.0, 1.0)
+                } else {
+                    Color::new(1.0, 0.0, 0.0)
+                }
+            });
+            let camera = Camera::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                PI / 2.0,
+                aspect_ratio,
+                0.0,
+                1.0,
+            );
+            let img = render(
+                Image::new(*width, *height),
+                &world,
+                &camera,
+                &RenderSettings::new(16, 10),
+            );
+            for h in 0..*height {
+                for w in 0..*width {
+                    let color = img.get_color_pixel(w, h);
+                    assert!((color.vec - Vec3::new(1.0, 1.0, 1.0)).length() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cat_eye_darkens_the_corners() {
+        let world = World::new(|_ray: &Ray| Color::new(1.0, 1.0, 1.0));
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            1.0,
+            1.0,
+        );
+        camera.lens.cat_eye = 1.0;
+        let img = render(
+            Image::new(5, 5),
+            &world,
+            &camera,
+            &RenderSettings::new(400, 10),
+        );
+
+        // Barely hidden in the middle, about half of the aperture is left in the corners
+        let (center, corner) = (img.get_color_pixel(2, 2), img.get_color_pixel(0, 0));
+        assert!(center.g() > 0.85);
+        assert!(corner.g() < 0.7 * center.g());
+    }
+
+    #[test]
+    fn progressive_passes_double_samples() {
+        let world = World::new(|_ray: &Ray| Color::new(0.5, 0.5, 0.5));
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let settings = RenderSettings::new(12, 10);
+        let mut snapshots = vec![];
+
+        let img = render_progressive(
+            Image::new(3, 2),
+            &world,
+            &camera,
+            &settings,
+            &Progressive::new(2, None),
+            &mut RenderControl::silent(),
+            &mut |img| {
+                snapshots.push(img.get_pixel_stats(0, 0).count);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(snapshots, vec![2, 4, 8, 12]);
+        assert_eq!(img.get_pixel_stats(2, 1).count, 12);
+
+        // Snapshots taken during the first pass miss pixels, they are written all the same
+        let path = std::env::temp_dir().join(format!("ray-tracer-test-{}.png", std::process::id()));
+        let mut missing = vec![];
+        render_progressive(
+            Image::new(3, 2),
+            &world,
+            &camera,
+            &settings,
+            &Progressive::new(2, Some(Duration::from_secs(0))),
+            &mut RenderControl::silent(),
+            &mut |img| {
+                missing.push(6 - img.stats.len());
+                crate::write_img_to_file(path.to_str().unwrap(), img)
+            },
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(missing[0], 3);
+    }
+
+    #[test]
+    fn cancelled_render_returns_partial_image() {
+        let world = World::new(|_ray: &Ray| Color::new(0.5, 0.5, 0.5));
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let cancellation = Cancellation::new();
+        let token = cancellation.clone();
+        let mut reports = vec![];
+        let mut control = RenderControl::new(
+            |progress: &Progress| {
+                reports.push(*progress);
+                if progress.rays_traced == 10 {
+                    token.cancel();
+                }
+            },
+            cancellation,
+        );
+        control.report_every = Duration::from_secs(0);
+
+        let img = render_with_control(
+            Image::new(4, 4),
+            &world,
+            &camera,
+            &RenderSettings::new(4, 10),
+            &mut control,
+        );
+        drop(control);
+
+        let traced: u32 = img.stats.values().map(|s| s.count).sum();
+        assert_eq!(traced, 10);
+        let path = std::env::temp_dir().join(format!("ray-tracer-test-{}.jpg", std::process::id()));
+        crate::write_img_to_file(path.to_str().unwrap(), &img).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reports.last().unwrap().rays_traced, 10);
+        assert_eq!(reports.last().unwrap().total_rays, 64);
+        assert!(reports.last().unwrap().eta().is_some());
+    }
+
+    #[test]
+    fn stereo_layouts() {
+        // The background tells on which side of the origin the rays start
+        let world = World::new(|ray: &Ray| {
+            if ray.origin.x < 0.0 {
+                Color::new(0.0, 0.0, 0.0)
+            } else {
+                Color::new(1.0, 1.0, 1.0)
+            }
+        });
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let settings = RenderSettings::new(1, 10);
+
+        for layout in [StereoLayout::SideBySide, StereoLayout::OverUnder].iter() {
+            let stereo = StereoCamera::new(&camera, 0.1, Convergence::Parallel, *layout);
+            let img = render_stereo(
+                Image::new(6, 4),
+                &world,
+                &stereo,
+                &settings,
+                &mut RenderControl::silent(),
+            );
+            assert_eq!(img.pixels.len(), 24);
+
+            let (left, right) = match layout {
+                StereoLayout::SideBySide => ((0, 0), (5, 3)),
+                StereoLayout::OverUnder => ((5, 3), (0, 0)),
+            };
+            assert_eq!(img.get_color_pixel(left.0, left.1).r(), 0.0);
+            assert_eq!(img.get_color_pixel(right.0, right.1).r(), 1.0);
+        }
+    }
+
+    #[test]
+    fn crop_keeps_the_rays_of_the_full_frame() {
+        // Black on the left of the camera, white on the right
+        let world = World::new(|ray: &Ray| {
+            let gray = if ray.direction.x < 0.0 { 0.0 } else { 1.0 };
+            Color::new(gray, gray, gray)
+        });
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            2.0,
+            0.0,
+            1.0,
+        );
+        let window = CropWindow::Normalized {
+            x_min: 0.0,
+            y_min: 0.5,
+            x_max: 0.25,
+            y_max: 1.0,
+        };
+        assert_eq!(window.tile(8, 4), Tile::new(0, 2, 2, 2));
+        assert_eq!(
+            CropWindow::Pixels(Tile::new(6, 3, 4, 4)).tile(8, 4),
+            Tile::new(6, 3, 2, 1)
+        );
+
+        let mut settings = RenderSettings::new(4, 10);
+        settings.crop = Some(window);
+        let img = render_with_control(
+            Image::new(8, 4),
+            &world,
+            &camera,
+            &settings,
+            &mut RenderControl::silent(),
+        );
+        assert!(img.pixels.keys().all(|(w, _)| *w < 4));
+
+        let cropped = apply_crop(&img, &window, &CropOutput::Window).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.pixels.len(), 4);
+        assert!(cropped
+            .pixels
+            .keys()
+            .all(|(w, h)| cropped.get_color_pixel(*w, *h).r() == 0.0));
+
+        let mut previous = Image::new(8, 4);
+        for h in 0..4 {
+            for w in 0..8 {
+                previous.add_pixel(w, h, Color::new(0.5, 0.5, 0.5));
+            }
+        }
+        let filled = apply_crop(&img, &window, &CropOutput::FillFrom(previous)).unwrap();
+        assert_eq!(filled.pixels.len(), 32);
+        assert_eq!(filled.get_color_pixel(1, 3).r(), 0.0);
+        assert_eq!(filled.get_color_pixel(1, 1).r(), 0.5);
+        let wrong_size = CropOutput::FillFrom(Image::new(4, 4));
+        assert!(apply_crop(&img, &window, &wrong_size).is_err());
+    }
+
+    #[test]
+    fn tiles_cover_the_image() {
+        let tiles = Tile::split(10, 7, 4);
+        assert_eq!(tiles.len(), 6);
+        for h in 0..7 {
+            for w in 0..10 {
+                assert_eq!(tiles.iter().filter(|t| t.contains(w, h)).count(), 1);
+            }
+        }
+        assert_eq!(tiles[5], Tile::new(8, 4, 2, 3));
+    }
+
+    #[test]
+    fn splat_normalizes_at_edges() {
+        let mut rng = rand::thread_rng();
+        let filter = Filter::Gaussian {
+            radius: 1.5,
+            alpha: 2.0,
+        };
+        let mut img = Image::new(8, 6);
+        let color = Color::new(0.25, 0.5, 1.0);
+
+        for h in 0..img.height {
+            for w in 0..img.width {
+                for _ in 0..4 {
+                    let x = w as f64 + rng.gen_range(0.0..1.0);
+                    let y = h as f64 + rng.gen_range(0.0..1.0);
+                    splat_sample(&mut img, &filter, x, y, color);
+                }
+            }
+        }
+
+        for h in 0..img.height {
+            for w in 0..img.width {
+                let pixel = img.get_color_pixel(w, h);
+                assert!((pixel.vec - color.vec).length() < 1e-9);
+            }
+        }
+
+        // Only reached by negative lobes
+        let mut img = Image::new(2, 1);
+        let filter = Filter::Lanczos { lobes: 2 };
+        splat_sample(&mut img, &filter, 0.1, 0.5, color);
+        assert!(img.weights[&(1, 0)] < 0.0);
+        assert_eq!(img.get_color_pixel(1, 0), Color::new(0.0, 0.0, 0.0));
+    }
+}
@@ -1,10 +1,18 @@
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use ray_tracer::{
     self,
-    math::{Sphere, Vec3, TAU},
-    Camera, Color, Dielectric, DiffuseLight, Image, Lambertian, Metal, Ray, World,
+    math::{Plane, Sphere, Vec3, TAU},
+    vfov_from_35mm_equivalent, AreaLight, Camera, CameraAnimation, Color, Dielectric, DiffuseLight,
+    DirectionalLight, Falloff, Image, Interpolation, Lambertian, Layered, Metal, PointLight, Ray,
+    SpotLight, Track, Translated, World,
 };
 
+const SCENE_SEED: u64 = 42;
+
+fn random_color(rng: &mut StdRng) -> Color {
+    Color::new(rng.gen(), rng.gen(), rng.gen())
+}
+
 #[allow(unused)]
 pub fn test_defocus_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32) {
     // Image
@@ -47,18 +55,11 @@ pub fn test_defocus_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u3
     let lookat = Vec3::new(0.0, 0.0, -1.0);
     let vup = Vec3::new(0.0, 1.0, 0.0);
     let aperture = 2.0;
-    let focus_dist = (lookfrom - lookat).length();
-    let vfov = 20.0 / 360.0 * TAU;
+    // Framed like a 60mm lens on a 35mm film
+    let vfov = vfov_from_35mm_equivalent(60.0, aspect_ratio);
 
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        focus_dist,
-    );
+    let mut camera = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, 1.0);
+    camera.autofocus(&world, 0.5, 0.5);
 
     (img, world, camera, samples_per_pixel, depth)
 }
@@ -82,13 +83,14 @@ pub fn random_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32
         )
     });
     let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
-    world.add(Sphere::new_boxed(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
+    world.add(Plane::new_boxed(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
         ground_material,
     ));
 
-    let mut rng = rand::thread_rng();
+    // Seeded so that the same scene is built every time (needed to resume a render)
+    let mut rng = StdRng::seed_from_u64(SCENE_SEED);
 
     for a in -11..11 {
         for b in -11..11 {
@@ -102,19 +104,19 @@ pub fn random_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32
             if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if (choose_mat < 0.8) {
                     // diffuse
-                    let albedo: Color = Color::new_random() * Color::new_random();
+                    let albedo: Color = random_color(&mut rng) * random_color(&mut rng);
                     let sphere_material = Lambertian::new(albedo);
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
                 } else if (choose_mat < 0.95) {
                     // metal
-                    let albedo = Color::new_random();
+                    let albedo = random_color(&mut rng);
                     let fuzz = rng.gen_range(0.0..0.5);
                     let sphere_material = Metal::new(albedo, fuzz);
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
                 } else {
                     // glass
                     let sphere_material =
-                        Dielectric::new(Color::new_random(), rng.gen_range(0.0..3.0));
+                        Dielectric::new(random_color(&mut rng), rng.gen_range(0.0..3.0));
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
                 }
             }
@@ -134,19 +136,12 @@ pub fn random_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32
     let lookfrom = Vec3::new(13.0, 2.0, 3.0);
     let lookat = Vec3::new(0.0, 0.0, 0.0);
     let vup = Vec3::new(0.0, 1.0, 0.0);
-    let vfov = 20.0 / 360.0 * TAU;
-    let aspect_ratio: f64 = 16.0 / 9.0;
+    // Framed like a 68mm lens on a 35mm film
+    let vfov = vfov_from_35mm_equivalent(68.0, aspect_ratio);
     let aperture = 0.1;
-    let focus_dist = 10.0;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        focus_dist,
-    );
+    let mut camera = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, 1.0);
+    // Sharp on the big glass sphere
+    camera.focus_on(Vec3::new(0.0, 1.0, 0.0));
 
     (img, world, camera, samples_per_pixel, depth)
 }
@@ -170,7 +165,8 @@ pub fn random_scene_with_lights() -> (Image, World<impl Fn(&Ray) -> Color>, Came
         ground_material,
     ));
 
-    let mut rng = rand::thread_rng();
+    // Seeded so that the same scene is built every time (needed to resume a render)
+    let mut rng = StdRng::seed_from_u64(SCENE_SEED);
 
     for a in -3..3 {
         for b in -3..3 {
@@ -184,24 +180,28 @@ pub fn random_scene_with_lights() -> (Image, World<impl Fn(&Ray) -> Color>, Came
             if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.25 {
                     // diffuse
-                    let albedo: Color = Color::new_random() * Color::new_random();
+                    let albedo: Color = random_color(&mut rng) * random_color(&mut rng);
                     let sphere_material = Lambertian::new(albedo);
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
-                } else if choose_mat >= 0.25 && choose_mat < 0.50 {
+                } else if (0.25..0.50).contains(&choose_mat) {
                     // metal
-                    let albedo = Color::new_random();
+                    let albedo = random_color(&mut rng);
                     let fuzz = rng.gen_range(0.0..0.5);
                     let sphere_material = Metal::new(albedo, fuzz);
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
-                } else if choose_mat >= 0.50 && choose_mat < 0.75 {
+                } else if (0.50..0.75).contains(&choose_mat) {
                     // emit light
-                    let emit = Color::new_random();
+                    let emit = random_color(&mut rng);
                     let sphere_material = DiffuseLight::new(emit);
-                    world.add(Sphere::new_boxed(center, 0.2, sphere_material));
+                    world.add_light(AreaLight::new_boxed(Sphere::new(
+                        center,
+                        0.2,
+                        sphere_material,
+                    )));
                 } else {
                     // glass
                     let sphere_material =
-                        Dielectric::new(Color::new_random(), rng.gen_range(0.0..3.0));
+                        Dielectric::new(random_color(&mut rng), rng.gen_range(0.0..3.0));
                     world.add(Sphere::new_boxed(center, 0.2, sphere_material));
                 }
             }
@@ -218,25 +218,165 @@ pub fn random_scene_with_lights() -> (Image, World<impl Fn(&Ray) -> Color>, Came
     world.add(Sphere::new_boxed(Vec3::new(4.0, 1.0, 0.0), 1.0, material3));
 
     let material4 = DiffuseLight::new(Color::new(8.0, 8.0, 8.0));
-    world.add(Sphere::new_boxed(Vec3::new(0.0, 4.0, 0.0), 1.0, material4));
+    world.add_light(AreaLight::new_boxed(Sphere::new(
+        Vec3::new(0.0, 4.0, 0.0),
+        1.0,
+        material4,
+    )));
 
     // Camera
     let lookfrom = Vec3::new(8.0, 2.0, 8.0);
     let lookat = Vec3::new(0.0, 0.0, 0.0);
     let vup = Vec3::new(0.0, 1.0, 0.0);
-    let vfov = 20.0 / 360.0 * TAU;
-    let aspect_ratio: f64 = 16.0 / 9.0;
+    // Framed like a 60mm lens on a 35mm film
+    let vfov = vfov_from_35mm_equivalent(60.0, aspect_ratio);
     let aperture = 0.1;
-    let focus_dist = 10.0;
+    let mut camera = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, 1.0);
+    // Sharp on the big glass sphere
+    camera.focus_on(Vec3::new(0.0, 1.0, 0.0));
+
+    (img, world, camera, samples_per_pixel, depth)
+}
+
+#[allow(unused)]
+pub fn product_shot_scene() -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32) {
+    // Image
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width: u32 = 800;
+    let image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
+    let img = Image::new(image_width, image_height);
+    let samples_per_pixel = 50;
+    let depth = 20;
+
+    // World
+    let mut world = World::new(|ray: &Ray| Color::new(0.05, 0.05, 0.05));
+    let ground_material = Lambertian::new(Color::new(0.8, 0.8, 0.8));
+    world.add(Sphere::new_boxed(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    ));
+
+    let varnished_wood = Layered::new(
+        Metal::new(Color::new(1.0, 1.0, 1.0), 0.0),
+        Lambertian::new(Color::new(0.4, 0.2, 0.1)),
+        1.5,
+    );
+    world.add(Sphere::new_boxed(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        varnished_wood,
+    ));
+
+    // Lights
+    world.add_light(Box::new(SpotLight::new(
+        Vec3::new(-3.0, 6.0, 3.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Color::new(60.0, 55.0, 50.0),
+        0.4,
+        0.3,
+        Falloff::Quadratic,
+    )));
+    world.add_light(Box::new(PointLight::new(
+        Vec3::new(4.0, 2.0, 2.0),
+        Color::new(6.0, 6.0, 8.0),
+        Falloff::Quadratic,
+    )));
+    world.add_light(Box::new(DirectionalLight::new(
+        Vec3::new(-1.0, -1.0, -1.0),
+        Color::new(0.3, 0.3, 0.3),
+    )));
+
+    // Camera
+    let lookfrom = Vec3::new(0.0, 2.0, 8.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    // Framed like a 48mm lens on a 35mm film
+    let vfov = vfov_from_35mm_equivalent(48.0, aspect_ratio);
+    let aperture = 0.0;
+    let mut camera = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, 1.0);
+    camera.autofocus(&world, 0.5, 0.5);
+
+    (img, world, camera, samples_per_pixel, depth)
+}
+
+// Turntable around a ball bouncing on a plate, `time` in seconds
+#[allow(unused)]
+pub fn turntable_scene(time: f64) -> (Image, World<impl Fn(&Ray) -> Color>, Camera, u32, u32) {
+    const PERIOD: f64 = 4.0;
+
+    // Image
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width: u32 = 400;
+    let image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
+    let img = Image::new(image_width, image_height);
+    let samples_per_pixel = 100;
+    let depth = 50;
+
+    // World
+    let bg = |ray: &Ray| {
+        let unit_direction: Vec3 = Vec3::unit(ray.direction);
+        let t = 0.5 * (unit_direction.y + 1.0);
+        Color::new_with_vec(
+            (1.0 - t) * Color::new(1.0, 1.0, 1.0).vec + t * Color::new(0.5, 0.7, 1.0).vec,
+        )
+    };
+    let mut world = World::new(bg);
+    world.add(Sphere::new_boxed(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Color::new(0.5, 0.5, 0.5)),
+    ));
+    world.add(Sphere::new_boxed(
+        Vec3::new(-2.0, 1.0, 0.0),
+        1.0,
+        Metal::new(Color::new(0.7, 0.6, 0.5), 0.0),
+    ));
+    world.add(Sphere::new_boxed(
+        Vec3::new(2.0, 1.0, 0.0),
+        1.0,
+        Dielectric::new(Color::new(1.0, 1.0, 1.0), 1.5),
+    ));
+
+    // Two bounces per turn, slowing down at the top
+    let mut bounce = Track::new(Interpolation::CatmullRom);
+    for (i, height) in [0.0, 1.5, 0.0, 1.5, 0.0].iter().enumerate() {
+        bounce.add_key(i as f64 * PERIOD / 4.0, Vec3::new(0.0, *height, 0.0));
+    }
+    let ball = Sphere::new_boxed(
+        Vec3::new(0.0, 0.5, 0.0),
+        0.5,
+        Lambertian::new(Color::new(0.8, 0.2, 0.1)),
+    );
+    world.add(Translated::new_boxed(
+        ball,
+        bounce.value_at(time % PERIOD).unwrap(),
+    ));
+
+    // Camera, keys all around the scene and one more on each side so that the loop is smooth
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let mut animation = CameraAnimation::new(Interpolation::CatmullRom, vup);
+    for i in -1..=9 {
+        let angle = i as f64 * TAU / 8.0;
+        let position = Vec3::new(8.0 * angle.sin(), 2.0, 8.0 * angle.cos());
+        animation
+            .position
+            .add_key(i as f64 * PERIOD / 8.0, position);
+    }
+    animation.lookat.add_key(0.0, Vec3::new(0.0, 0.75, 0.0));
+
+    // Framed like a 40mm lens on a 35mm film
+    let vfov = vfov_from_35mm_equivalent(40.0, aspect_ratio);
     let camera = Camera::new(
-        lookfrom,
-        lookat,
+        Vec3::new(0.0, 2.0, 8.0),
+        Vec3::new(0.0, 0.75, 0.0),
         vup,
         vfov,
         aspect_ratio,
-        aperture,
-        focus_dist,
+        0.0,
+        8.0,
     );
+    let camera = animation.camera_at(&camera, time % PERIOD).unwrap();
 
     (img, world, camera, samples_per_pixel, depth)
 }
@@ -0,0 +1,340 @@
+use crate::{image::to_rgb8, Image, RTError};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbImage, RgbaImage,
+};
+use std::{collections::HashMap, fs::File, io::BufWriter};
+
+// How the colors missing from the palette are made up: the nearest color of the palette is taken,
+// or the error is spread over the next pixels so that on average the color is right
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dithering {
+    None,
+    FloydSteinberg,
+}
+
+// At most 256 colors shared by all the frames of an animation, so that they do not flicker from a
+// frame to the next
+#[derive(Debug, PartialEq, Clone)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    // Median cut: the box of colors spreading the most is split in two at the median of its
+    // widest channel, until there are `size` boxes, each giving its average color
+    pub fn median_cut(frames: &[RgbImage], size: usize) -> Self {
+        let mut histogram: HashMap<[u8; 3], u64> = HashMap::new();
+        for frame in frames {
+            for pixel in frame.pixels() {
+                *histogram.entry(pixel.0).or_insert(0) += 1;
+            }
+        }
+
+        let mut boxes: Vec<Vec<([u8; 3], u64)>> = vec![histogram.into_iter().collect()];
+        while boxes.len() < size {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, colors)| colors.len() > 1)
+                .map(|(i, colors)| (i, widest_channel(colors)))
+                .max_by_key(|(_, (_, extent))| *extent);
+            let (i, (channel, _)) = match widest {
+                Some(widest) => widest,
+                None => break,
+            };
+
+            let mut colors = boxes.swap_remove(i);
+            colors.sort_unstable_by_key(|(color, _)| color[channel]);
+            let total: u64 = colors.iter().map(|(_, count)| count).sum();
+            let mut below = 0;
+            let median = colors
+                .iter()
+                .position(|(_, count)| {
+                    below += count;
+                    2 * below >= total
+                })
+                .unwrap_or(0);
+            // Both halves keep at least one color
+            let upper = colors.split_off((median + 1).min(colors.len() - 1));
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+
+        let colors = boxes
+            .iter()
+            .filter(|colors| !colors.is_empty())
+            .map(|colors| {
+                let total: u64 = colors.iter().map(|(_, count)| count).sum();
+                let mut mean = [0u8; 3];
+                for (c, channel) in mean.iter_mut().enumerate() {
+                    let sum: u64 = colors.iter().map(|(color, n)| color[c] as u64 * n).sum();
+                    *channel = ((sum + total / 2) / total) as u8;
+                }
+                mean
+            })
+            .collect();
+        Palette { colors }
+    }
+
+    // Index of the color of the palette closest to `color`
+    pub fn nearest(&self, color: [f64; 3]) -> usize {
+        let distance = |c: &[u8; 3]| {
+            (0..3)
+                .map(|i| (c[i] as f64 - color[i]).powi(2))
+                .sum::<f64>()
+        };
+        (0..self.colors.len())
+            .min_by(|a, b| {
+                distance(&self.colors[*a])
+                    .partial_cmp(&distance(&self.colors[*b]))
+                    .unwrap()
+            })
+            .unwrap_or(0)
+    }
+
+    // `frame` drawn with the colors of the palette only
+    pub fn quantize(&self, frame: &RgbImage, dithering: Dithering) -> RgbImage {
+        let (width, height) = frame.dimensions();
+        let mut quantized = RgbImage::new(width, height);
+        let mut cache: HashMap<[u8; 3], usize> = HashMap::new();
+        // Error carried to the pixels not drawn yet, this row and the next one
+        let mut errors = vec![[0.0; 3]; (width * 2) as usize];
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = frame.get_pixel(x, y).0;
+                let i = x as usize;
+                let wanted = [
+                    pixel[0] as f64 + errors[i][0],
+                    pixel[1] as f64 + errors[i][1],
+                    pixel[2] as f64 + errors[i][2],
+                ];
+                let key = [
+                    wanted[0].round().clamp(0.0, 255.0) as u8,
+                    wanted[1].round().clamp(0.0, 255.0) as u8,
+                    wanted[2].round().clamp(0.0, 255.0) as u8,
+                ];
+                let index = *cache
+                    .entry(key)
+                    .or_insert_with(|| self.nearest([key[0] as f64, key[1] as f64, key[2] as f64]));
+                let color = self.colors[index];
+                quantized.put_pixel(x, y, image::Rgb(color));
+
+                if dithering == Dithering::FloydSteinberg {
+                    let w = width as usize;
+                    for c in 0..3 {
+                        let error = wanted[c] - color[c] as f64;
+                        if x + 1 < width {
+                            errors[i + 1][c] += error * 7.0 / 16.0;
+                            errors[w + i + 1][c] += error / 16.0;
+                        }
+                        if x > 0 {
+                            errors[w + i - 1][c] += error * 3.0 / 16.0;
+                        }
+                        errors[w + i][c] += error * 5.0 / 16.0;
+                    }
+                }
+            }
+            // The next row becomes the current one
+            let w = width as usize;
+            errors.copy_within(w.., 0);
+            errors[w..].iter_mut().for_each(|error| *error = [0.0; 3]);
+        }
+
+        quantized
+    }
+}
+
+// Channel with the largest range of values, and that range
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let min = colors.iter().map(|(color, _)| color[c]).min().unwrap_or(0);
+            let max = colors.iter().map(|(color, _)| color[c]).max().unwrap_or(0);
+            (c, max - min)
+        })
+        .max_by_key(|(_, extent)| *extent)
+        .unwrap()
+}
+
+// Size shared by all the frames of an animation
+fn frame_size(frames: &[RgbImage]) -> Result<(u32, u32), RTError> {
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.dimensions(),
+        None => return Err(RTError::EmptyImg),
+    };
+    if frames
+        .iter()
+        .any(|frame| frame.dimensions() != (width, height))
+    {
+        return Err(RTError::InvalidArgument(
+            "the frames of an animation must have the same size".to_string(),
+        ));
+    }
+    Ok((width, height))
+}
+
+// Time between two frames at `fps` frames per second, in seconds as a fraction whose terms are at
+// most `max`. It is the last convergent of the continued fraction of 1 / fps that fits, so 24 fps
+// gives 1/24 s and 29.97 fps 100/2997 s
+fn frame_delay(fps: f64, max: u32) -> Result<(u32, u32), RTError> {
+    let delay = 1.0 / fps;
+    if !(fps <= max as f64 && delay <= max as f64) {
+        return Err(RTError::InvalidArgument(format!(
+            "{} frames per second is out of range",
+            fps
+        )));
+    }
+
+    let (mut numer, mut denom) = (delay.floor() as u64, 1);
+    let (mut previous_numer, mut previous_denom) = (1, 0);
+    let mut rest = delay - delay.floor();
+    while rest > 1e-9 {
+        let inverse = 1.0 / rest;
+        let a = inverse.floor();
+        if a > max as f64 {
+            break;
+        }
+        let next = (
+            a as u64 * numer + previous_numer,
+            a as u64 * denom + previous_denom,
+        );
+        if next.0 > max as u64 || next.1 > max as u64 {
+            break;
+        }
+        (previous_numer, previous_denom) = (numer, denom);
+        (numer, denom) = next;
+        rest = inverse - a;
+    }
+    Ok((numer as u32, denom as u32))
+}
+
+// Animated GIF looping forever, with a palette of 256 colors computed over all the frames
+pub fn write_gif(
+    path: &str,
+    frames: &[Image],
+    fps: f64,
+    dithering: Dithering,
+) -> Result<(), RTError> {
+    let frames = frames.iter().map(to_rgb8).collect::<Result<Vec<_>, _>>()?;
+    frame_size(&frames)?;
+    let (numer, denom) = frame_delay(fps, u32::MAX / 1000)?;
+    let palette = Palette::median_cut(&frames, 256);
+
+    let file = File::create(path).map_err(RTError::IO)?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(RTError::ImageRS)?;
+    let delay = Delay::from_numer_denom_ms(1000 * numer, denom);
+    for frame in frames.iter() {
+        // With 256 colors at most the encoder keeps them as they are
+        let quantized = palette.quantize(frame, dithering);
+        let rgba = RgbaImage::from_fn(frame.width(), frame.height(), |x, y| {
+            let [r, g, b] = quantized.get_pixel(x, y).0;
+            image::Rgba([r, g, b, 255])
+        });
+        encoder
+            .encode_frame(Frame::from_parts(rgba, 0, 0, delay))
+            .map_err(RTError::ImageRS)?;
+    }
+
+    Ok(())
+}
+
+// Animated PNG looping forever, in true colors
+pub fn write_apng(path: &str, frames: &[Image], fps: f64) -> Result<(), RTError> {
+    let frames = frames.iter().map(to_rgb8).collect::<Result<Vec<_>, _>>()?;
+    let (width, height) = frame_size(&frames)?;
+    let (numer, denom) = frame_delay(fps, u16::MAX as u32)?;
+
+    let file = File::create(path).map_err(RTError::IO)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(RTError::Png)?;
+    encoder
+        .set_frame_delay(numer as u16, denom as u16)
+        .map_err(RTError::Png)?;
+
+    let mut writer = encoder.write_header().map_err(RTError::Png)?;
+    for frame in frames.iter() {
+        writer
+            .write_image_data(frame.as_raw())
+            .map_err(RTError::Png)?;
+    }
+    writer.finish().map_err(RTError::Png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        // Two colors only, kept as they are
+        let frame = RgbImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let palette = Palette::median_cut(std::slice::from_ref(&frame), 256);
+        assert_eq!(palette.colors.len(), 2);
+        assert_eq!(palette.quantize(&frame, Dithering::FloydSteinberg), frame);
+
+        // A gradient drawn with black and white: dithered the average stays the same
+        let gradient = RgbImage::from_fn(64, 16, |x, _| {
+            let gray = (x * 4) as u8;
+            image::Rgb([gray, gray, gray])
+        });
+        let palette = Palette {
+            colors: vec![[0, 0, 0], [255, 255, 255]],
+        };
+        let mean = |img: &RgbImage| {
+            img.pixels().map(|p| p[0] as f64).sum::<f64>() / (img.width() * img.height()) as f64
+        };
+        let dithered = palette.quantize(&gradient, Dithering::FloydSteinberg);
+        assert!((mean(&dithered) - mean(&gradient)).abs() < 2.0);
+        let nearest = palette.quantize(&gradient, Dithering::None);
+        assert!(nearest
+            .pixels()
+            .zip(gradient.pixels())
+            .all(|(p, g)| p[0] == if g[0] < 128 { 0 } else { 255 }));
+
+        let mut many = Palette::median_cut(&[gradient], 4);
+        assert_eq!(many.colors.len(), 4);
+        many.colors.sort();
+        assert!(many.colors[0][0] < 64 && many.colors[3][0] > 192);
+    }
+
+    #[test]
+    fn animations() {
+        assert_eq!(frame_delay(24.0, 65535).unwrap(), (1, 24));
+        assert_eq!(frame_delay(29.97, 65535).unwrap(), (100, 2997));
+        assert_eq!(frame_delay(0.01, 65535).unwrap(), (100, 1));
+        assert_eq!(frame_delay(0.001, 65535).unwrap(), (1000, 1));
+        assert!(frame_delay(1e-6, 65535).is_err());
+        assert!(frame_delay(f64::NAN, 65535).is_err());
+
+        let path = std::env::temp_dir().join(format!("ray-tracer-test-{}.gif", std::process::id()));
+        let path = path.to_str().unwrap();
+        let frame = |width, height| {
+            let mut img = Image::new(width, height);
+            for h in 0..height {
+                for w in 0..width {
+                    img.add_pixel(w, h, crate::Color::new(0.5, 0.5, 0.5));
+                }
+            }
+            img
+        };
+        assert!(write_gif(path, &[frame(2, 2), frame(3, 2)], 24.0, Dithering::None).is_err());
+        assert!(write_apng(path, &[frame(2, 2), frame(3, 2)], 24.0).is_err());
+        write_gif(path, &[frame(2, 2), frame(2, 2)], 0.01, Dithering::None).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+}
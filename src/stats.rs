@@ -0,0 +1,175 @@
+use crate::RTError;
+use std::{cell::RefCell, fs, time::Duration};
+
+// Counters gathered while rendering. Rays are split between the primary ones leaving the camera,
+// the secondary ones scattered by the materials and the shadow rays testing the lights, and each
+// path is counted once in how it ended. There is no acceleration structure: every ray is tested
+// against every object, `intersection_tests` counts the shapes tested
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub primary_rays: u64,
+    pub secondary_rays: u64,
+    pub shadow_rays: u64,
+    pub intersection_tests: u64,
+    pub paths_depth_limit: u64,
+    pub paths_absorbed: u64,
+    pub paths_escaped: u64,
+    pub paths_russian_roulette: u64,
+    pub threads: u32,
+    pub elapsed: Duration,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.secondary_rays + self.shadow_rays
+    }
+
+    pub fn rays_per_second_per_thread(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64() * self.threads.max(1) as f64;
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.rays() as f64 / seconds
+        }
+    }
+
+    // Number of segments of a path, shadow rays aside
+    pub fn average_path_length(&self) -> f64 {
+        if self.primary_rays == 0 {
+            0.0
+        } else {
+            (self.primary_rays + self.secondary_rays) as f64 / self.primary_rays as f64
+        }
+    }
+
+    pub fn intersection_tests_per_ray(&self) -> f64 {
+        if self.rays() == 0 {
+            0.0
+        } else {
+            self.intersection_tests as f64 / self.rays() as f64
+        }
+    }
+
+    // Add the counters of another render, done after or alongside this one
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+        self.paths_depth_limit += other.paths_depth_limit;
+        self.paths_absorbed += other.paths_absorbed;
+        self.paths_escaped += other.paths_escaped;
+        self.paths_russian_roulette += other.paths_russian_roulette;
+        self.threads = self.threads.max(other.threads);
+        self.elapsed += other.elapsed;
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{
+  "primary_rays": {},
+  "secondary_rays": {},
+  "shadow_rays": {},
+  "threads": {},
+  "elapsed_seconds": {},
+  "rays_per_second_per_thread": {},
+  "average_path_length": {},
+  "paths": {{
+    "depth_limit": {},
+    "absorbed": {},
+    "escaped": {},
+    "russian_roulette": {}
+  }},
+  "intersection_tests": {},
+  "intersection_tests_per_ray": {}
+}}
+"#,
+            self.primary_rays,
+            self.secondary_rays,
+            self.shadow_rays,
+            self.threads,
+            self.elapsed.as_secs_f64(),
+            self.rays_per_second_per_thread(),
+            self.average_path_length(),
+            self.paths_depth_limit,
+            self.paths_absorbed,
+            self.paths_escaped,
+            self.paths_russian_roulette,
+            self.intersection_tests,
+            self.intersection_tests_per_ray()
+        )
+    }
+}
+
+pub fn write_stats_to_file(path: &str, stats: &RenderStats) -> Result<(), RTError> {
+    fs::write(path, stats.to_json()).map_err(RTError::IO)
+}
+
+// The shapes and the integrator count into the stats of their thread, the render collects them
+thread_local! {
+    static THREAD_STATS: RefCell<RenderStats> = RefCell::new(RenderStats::default());
+}
+
+pub(crate) fn count(f: impl FnOnce(&mut RenderStats)) {
+    THREAD_STATS.with(|stats| f(&mut stats.borrow_mut()));
+}
+
+pub(crate) fn count_intersection_test() {
+    count(|stats| stats.intersection_tests += 1);
+}
+
+// Get the stats counted by this thread since the last call, and start over
+pub(crate) fn take_thread_stats() -> RenderStats {
+    THREAD_STATS.with(|stats| stats.replace(RenderStats::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        math::{Sphere, Vec3},
+        render_with_control, Camera, Color, Image, Lambertian, Ray, RenderControl, RenderSettings,
+        World,
+    };
+
+    #[test]
+    fn paths_inside_a_sphere_hit_the_depth_limit() {
+        // The camera is inside a closed diffuse sphere, no ray can escape
+        let mut world = World::new(|_ray: &Ray| Color::new(1.0, 1.0, 1.0));
+        world.add(Sphere::new_boxed(
+            Vec3::new(0.0, 0.0, 0.0),
+            10.0,
+            Lambertian::new(Color::new(0.5, 0.5, 0.5)),
+        ));
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut control = RenderControl::silent();
+
+        render_with_control(
+            Image::new(4, 4),
+            &world,
+            &camera,
+            &RenderSettings::new(2, 3),
+            &mut control,
+        );
+
+        let stats = control.stats;
+        assert_eq!(stats.primary_rays, 32);
+        assert_eq!(stats.secondary_rays, 64);
+        assert_eq!(stats.shadow_rays, 0);
+        assert_eq!(stats.paths_depth_limit, 32);
+        assert_eq!(
+            stats.paths_escaped + stats.paths_absorbed + stats.paths_russian_roulette,
+            0
+        );
+        assert_eq!(stats.intersection_tests_per_ray(), 1.0);
+        assert_eq!(stats.average_path_length(), 3.0);
+        assert!(stats.to_json().contains("\"depth_limit\": 32"));
+    }
+}
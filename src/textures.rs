@@ -0,0 +1,121 @@
+use crate::{clamp, math::Vec3, Color, RTError, StableHasher};
+use std::hash::Hasher;
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, point: &Vec3) -> Color;
+
+    // See `Hittable::fingerprint`
+    fn fingerprint(&self, hasher: &mut StableHasher);
+}
+
+// A plain color is a texture with the same value everywhere
+impl Texture for Color {
+    fn value(&self, _u: f64, _v: f64, _point: &Vec3) -> Color {
+        *self
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+// A plain number is a grey texture, handy for weights
+impl Texture for f64 {
+    fn value(&self, _u: f64, _v: f64, _point: &Vec3) -> Color {
+        Color::new(*self, *self, *self)
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(self);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Checker<E: Texture, O: Texture> {
+    pub even: E,
+    pub odd: O,
+    pub scale: f64,
+}
+
+impl<E: Texture, O: Texture> Checker<E, O> {
+    pub fn new(even: E, odd: O, scale: f64) -> Self {
+        Checker { even, odd, scale }
+    }
+}
+
+impl<E: Texture, O: Texture> Texture for Checker<E, O> {
+    fn value(&self, u: f64, v: f64, point: &Vec3) -> Color {
+        let sines = (self.scale * point.x).sin()
+            * (self.scale * point.y).sin()
+            * (self.scale * point.z).sin();
+        if sines < 0.0 {
+            self.odd.value(u, v, point)
+        } else {
+            self.even.value(u, v, point)
+        }
+    }
+
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("Checker", self.scale));
+        self.even.fingerprint(hasher);
+        self.odd.fingerprint(hasher);
+    }
+}
+
+// A picture wrapped using the (u, v) coordinates of the hit, like a screen or a neon sign
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageTexture {
+    pub pixels: Vec<Color>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageTexture {
+    pub fn open(path: &str) -> Result<Self, RTError> {
+        let img = image::open(path).map_err(RTError::ImageRS)?.to_rgb8();
+        let (width, height) = img.dimensions();
+        // Pictures are stored with a gamma of 2, like the ones we write
+        let pixels = img
+            .pixels()
+            .map(|p| {
+                Color::new(
+                    (p[0] as f64 / 255.0).powi(2),
+                    (p[1] as f64 / 255.0).powi(2),
+                    (p[2] as f64 / 255.0).powi(2),
+                )
+            })
+            .collect();
+
+        Ok(ImageTexture {
+            pixels,
+            width,
+            height,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Vec3) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // v goes up while the rows of the picture go down
+        let u = clamp(u, 0.0, 1.0);
+        let v = 1.0 - clamp(v, 0.0, 1.0);
+        let i = ((u * self.width as f64) as u32).min(self.width - 1);
+        let j = ((v * self.height as f64) as u32).min(self.height - 1);
+
+        self.pixels[(j * self.width + i) as usize]
+    }
+
+    // The pixels are hashed as they are, their debug representation would be slow to make
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        hasher.write_debug(&("ImageTexture", self.width, self.height));
+        for pixel in self.pixels.iter() {
+            hasher.write_u64(pixel.r().to_bits());
+            hasher.write_u64(pixel.g().to_bits());
+            hasher.write_u64(pixel.b().to_bits());
+        }
+    }
+}
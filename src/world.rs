@@ -1,4 +1,7 @@
-use crate::{Color, HitRecord, Hittable, Ray};
+use crate::{
+    math::{self, Vec3},
+    stats, Color, HitRecord, Hittable, Light, Material, Ray, StableHasher,
+};
 // use std::fmt::Debug;
 
 pub struct World<F>
@@ -6,6 +9,7 @@ where
     F: Fn(&Ray) -> Color,
 {
     pub objects: Vec<Box<dyn Hittable>>,
+    pub lights: Vec<Box<dyn Light>>,
     pub background: F,
 }
 
@@ -16,6 +20,7 @@ where
     pub fn new(background: F) -> Self {
         World {
             objects: vec![],
+            lights: vec![],
             background,
         }
     }
@@ -24,8 +29,73 @@ where
         self.objects.push(object);
     }
 
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.lights.clear();
+    }
+
+    // Light received directly from the lights, each one is checked with a shadow ray
+    pub fn direct_lighting(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        material: &dyn Material,
+    ) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        for light in self.lights.iter() {
+            if let Some(sample) = light.sample(&hit_record.point) {
+                let f = material.eval(ray_in, hit_record, &sample.direction);
+                if f.vec == Vec3::new(0.0, 0.0, 0.0) {
+                    continue;
+                }
+
+                // Stop a bit before the light so that area lights do not shadow themselves
+                let shadow_ray = Ray::new(hit_record.point, sample.direction);
+                stats::count(|stats| stats.shadow_rays += 1);
+                if self
+                    .hit(&shadow_ray, 0.001, sample.distance * (1.0 - 1e-6) - 0.001)
+                    .is_none()
+                {
+                    color = color + f * sample.radiance;
+                }
+            }
+        }
+        color
+    }
+
+    // Same as `hit` but also tells if what was hit is one of the lights
+    pub fn hit_with_lights(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(HitRecord<'_>, bool)> {
+        self.hit_object(r, t_min, t_max)
+            .map(|(hit, index)| (hit, index >= self.objects.len()))
+    }
+
+    // Same as `hit` but also gives the index of what was hit, the lights being numbered after the objects
+    pub fn hit_object(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord<'_>, usize)> {
+        let mut closest_so_far = t_max;
+        let mut hit_anything: Option<(HitRecord, usize)> = None;
+        let lights = self.lights.iter().map(|l| l.hittable());
+        for (index, h) in self
+            .objects
+            .iter()
+            .map(|o| Some(o.as_ref()))
+            .chain(lights)
+            .enumerate()
+        {
+            if let Some(hit) = h.and_then(|h| h.hit(r, t_min, closest_so_far)) {
+                closest_so_far = hit.t;
+                hit_anything = Some((hit, index));
+            }
+        }
+        hit_anything
     }
 }
 
@@ -33,16 +103,34 @@ impl<F> Hittable for World<F>
 where
     F: Fn(&Ray) -> Color,
 {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut closest_so_far = t_max;
-        let mut hit_anything: Option<HitRecord> = None;
-        for h in self.objects.iter() {
-            if let Some(hit) = h.hit(r, t_min, closest_so_far) {
-                closest_so_far = hit.t;
-                hit_anything = Some(hit);
-            }
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.hit_with_lights(r, t_min, t_max).map(|(hit, _)| hit)
+    }
+
+    // The background being a function, it is only known by what it gives in a few hundred
+    // directions spread evenly over the sphere
+    fn fingerprint(&self, hasher: &mut StableHasher) {
+        const DIRECTIONS: u32 = 256;
+        hasher.write_debug(&("World", self.objects.len(), self.lights.len()));
+        for object in self.objects.iter() {
+            object.fingerprint(hasher);
+        }
+        for light in self.lights.iter() {
+            light.fingerprint(hasher);
+        }
+
+        // Fibonacci sphere: even steps along z, turning by the golden angle
+        let golden_angle = math::PI * (3.0 - 5f64.sqrt());
+        for i in 0..DIRECTIONS {
+            let z = 1.0 - (2.0 * i as f64 + 1.0) / DIRECTIONS as f64;
+            let r = (1.0 - z * z).sqrt();
+            let angle = i as f64 * golden_angle;
+            let direction = Vec3::new(r * angle.cos(), r * angle.sin(), z);
+            hasher.write_debug(&(self.background)(&Ray::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                direction,
+            )));
         }
-        hit_anything
     }
 }
 
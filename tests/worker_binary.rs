@@ -0,0 +1,55 @@
+use std::{
+    env, fs,
+    net::TcpListener,
+    process::{self, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+// The coordinator and the worker as separate processes, only a 2x2 window of the scene to be quick
+#[test]
+fn worker_binary_renders_for_the_coordinator() {
+    let binary = env!("CARGO_BIN_EXE_ray-tracer");
+    let dir = env::temp_dir().join(format!("ray-tracer-workers-{}", process::id()));
+    fs::create_dir_all(dir.join("target")).unwrap();
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let address = format!("127.0.0.1:{}", port);
+    let run = |args: &[&str]| {
+        let mut command = Command::new(binary);
+        command
+            .current_dir(&dir)
+            .args(args)
+            .args(["--crop", "0", "0", "2", "2"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        command
+    };
+
+    let mut coordinator = run(&["--coordinator", &address, "--worker-timeout", "30"])
+        .spawn()
+        .unwrap();
+    // Retried until the coordinator listens
+    let worked = (0..100).any(|_| {
+        let done = run(&["--worker", &address]).status().unwrap().success();
+        if !done {
+            thread::sleep(Duration::from_millis(100));
+        }
+        done
+    });
+    assert!(worked);
+    assert!(coordinator.wait().unwrap().success());
+    let img = ray_tracer::read_img_from_file(dir.join("target/img.jpg").to_str().unwrap()).unwrap();
+    assert_eq!((img.width, img.height), (2, 2));
+
+    // Nobody comes this time
+    let alone = run(&["--coordinator", &address, "--worker-timeout", "0.5"])
+        .status()
+        .unwrap();
+    assert!(!alone.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}